@@ -0,0 +1,412 @@
+/*
+File type bits of a Unix mode (S_IFMT) and their values
+*/
+const S_IFMT: u32 = 0o170000;
+const S_IFSOCK: u32 = 0o140000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFIFO: u32 = 0o010000;
+
+/*
+7-Zip and others set this DOS attribute bit on entries from other hosts, to say the
+high 16 bits of the external attributes hold a Unix mode
+*/
+const DOS_UNIX_EXTENSION: u32 = 0x8000;
+
+/*
+The system an entry was written on, from the high byte of `version made by`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostSystem {
+    MsDos,
+    Amiga,
+    OpenVms,
+    Unix,
+    VmCms,
+    AtariSt,
+    Os2Hpfs,
+    Macintosh,
+    ZSystem,
+    CpM,
+    Ntfs,
+    Mvs,
+    Vse,
+    AcornRisc,
+    Vfat,
+    AlternateMvs,
+    BeOs,
+    Tandem,
+    Os400,
+    MacOs,
+    Unknown(u8),
+}
+
+/*
+The type of the file an entry was made from.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    Fifo,
+    CharacterDevice,
+    BlockDevice,
+    Socket,
+}
+
+/*
+The permission and file type bits of a Unix `st_mode`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixMode(pub u32);
+
+/*
+The MS-DOS attribute byte, also used by NTFS and VFAT hosts.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DosAttributes(pub u8);
+
+/*
+The external file attributes of an entry, interpreted for the host it was
+written on.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileAttributes {
+    pub host_system: HostSystem,
+    /*
+    The high 16 bits, when the host stores a Unix mode there.
+    */
+    pub unix_mode: Option<UnixMode>,
+    /*
+    The low byte, which every host fills in the MS-DOS way.
+    */
+    pub dos: DosAttributes,
+    /*
+    The entry name ends with a slash, which marks a directory on every host.
+    */
+    pub directory_name: bool,
+}
+
+impl HostSystem {
+    pub fn from_version_made_by(version_made_by: u16) -> Self {
+        match version_made_by >> 8 {
+            0 => HostSystem::MsDos,
+            1 => HostSystem::Amiga,
+            2 => HostSystem::OpenVms,
+            3 => HostSystem::Unix,
+            4 => HostSystem::VmCms,
+            5 => HostSystem::AtariSt,
+            6 => HostSystem::Os2Hpfs,
+            7 => HostSystem::Macintosh,
+            8 => HostSystem::ZSystem,
+            9 => HostSystem::CpM,
+            10 => HostSystem::Ntfs,
+            11 => HostSystem::Mvs,
+            12 => HostSystem::Vse,
+            13 => HostSystem::AcornRisc,
+            14 => HostSystem::Vfat,
+            15 => HostSystem::AlternateMvs,
+            16 => HostSystem::BeOs,
+            17 => HostSystem::Tandem,
+            18 => HostSystem::Os400,
+            19 => HostSystem::MacOs,
+            host => HostSystem::Unknown(host as u8),
+        }
+    }
+
+    pub fn stores_unix_mode(&self) -> bool {
+        /*
+        Hosts whose archivers put a Unix mode into the high 16 bits of the
+        external attributes
+        */
+        matches!(self, HostSystem::Unix | HostSystem::MacOs | HostSystem::BeOs | HostSystem::Os400)
+    }
+}
+
+impl std::fmt::Display for HostSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HostSystem::MsDos => write!(f, "MS-DOS"),
+            HostSystem::Amiga => write!(f, "Amiga"),
+            HostSystem::OpenVms => write!(f, "OpenVMS"),
+            HostSystem::Unix => write!(f, "Unix"),
+            HostSystem::VmCms => write!(f, "VM/CMS"),
+            HostSystem::AtariSt => write!(f, "Atari ST"),
+            HostSystem::Os2Hpfs => write!(f, "OS/2 HPFS"),
+            HostSystem::Macintosh => write!(f, "Macintosh"),
+            HostSystem::ZSystem => write!(f, "Z-System"),
+            HostSystem::CpM => write!(f, "CP/M"),
+            HostSystem::Ntfs => write!(f, "NTFS"),
+            HostSystem::Mvs => write!(f, "MVS"),
+            HostSystem::Vse => write!(f, "VSE"),
+            HostSystem::AcornRisc => write!(f, "Acorn RISC OS"),
+            HostSystem::Vfat => write!(f, "VFAT"),
+            HostSystem::AlternateMvs => write!(f, "MVS (alternate)"),
+            HostSystem::BeOs => write!(f, "BeOS"),
+            HostSystem::Tandem => write!(f, "Tandem"),
+            HostSystem::Os400 => write!(f, "OS/400"),
+            HostSystem::MacOs => write!(f, "macOS"),
+            HostSystem::Unknown(host) => write!(f, "unknown ({})", host),
+        }
+    }
+}
+
+impl std::fmt::Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileType::Regular => write!(f, "regular file"),
+            FileType::Directory => write!(f, "directory"),
+            FileType::Symlink => write!(f, "symbolic link"),
+            FileType::Fifo => write!(f, "FIFO"),
+            FileType::CharacterDevice => write!(f, "character device"),
+            FileType::BlockDevice => write!(f, "block device"),
+            FileType::Socket => write!(f, "socket"),
+        }
+    }
+}
+
+impl UnixMode {
+    pub fn file_type(&self) -> Option<FileType> {
+        match self.0 & S_IFMT {
+            S_IFSOCK => Some(FileType::Socket),
+            S_IFLNK => Some(FileType::Symlink),
+            S_IFREG => Some(FileType::Regular),
+            S_IFBLK => Some(FileType::BlockDevice),
+            S_IFDIR => Some(FileType::Directory),
+            S_IFCHR => Some(FileType::CharacterDevice),
+            S_IFIFO => Some(FileType::Fifo),
+            _ => None,
+        }
+    }
+
+    pub fn permissions(&self) -> u32 {
+        self.0 & 0o777
+    }
+
+    pub fn is_setuid(&self) -> bool {
+        self.0 & 0o4000 != 0
+    }
+
+    pub fn is_setgid(&self) -> bool {
+        self.0 & 0o2000 != 0
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.0 & 0o1000 != 0
+    }
+
+    fn permission_string(&self) -> String {
+        /*
+        rwxrwxrwx, with the execute bit of the owner, group and others replaced by
+        s/S, s/S and t/T for setuid, setgid and sticky, as ls -l does
+        */
+        let bit = |mask: u32, character: char| match self.0 & mask != 0 {
+            true => character,
+            false => '-',
+        };
+        let special = |execute: u32, special: bool, set: char| match (self.0 & execute != 0, special) {
+            (true, true) => set,
+            (false, true) => set.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        };
+
+        [
+            bit(0o400, 'r'),
+            bit(0o200, 'w'),
+            special(0o100, self.is_setuid(), 's'),
+            bit(0o040, 'r'),
+            bit(0o020, 'w'),
+            special(0o010, self.is_setgid(), 's'),
+            bit(0o004, 'r'),
+            bit(0o002, 'w'),
+            special(0o001, self.is_sticky(), 't'),
+        ]
+        .iter()
+        .collect()
+    }
+}
+
+impl DosAttributes {
+    pub fn is_read_only(&self) -> bool {
+        self.0 & 0x01 != 0
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.0 & 0x02 != 0
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.0 & 0x10 != 0
+    }
+
+    pub fn is_archive(&self) -> bool {
+        self.0 & 0x20 != 0
+    }
+}
+
+impl std::fmt::Display for DosAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        /*
+        One column per attribute, in the order of the attrib command: RHSDA
+        */
+        let flags = [
+            (self.is_read_only(), 'R'),
+            (self.is_hidden(), 'H'),
+            (self.is_system(), 'S'),
+            (self.is_directory(), 'D'),
+            (self.is_archive(), 'A'),
+        ];
+        let columns: String = flags
+            .iter()
+            .map(|(set, character)| match set {
+                true => *character,
+                false => '-',
+            })
+            .collect();
+        write!(f, "{}", columns)
+    }
+}
+
+impl FileAttributes {
+    pub fn new(version_made_by: u16, external_file_attributes: u32, directory_name: bool) -> Self {
+        /*
+        # Arguments
+        * `version_made_by: u16` The version made by field of the CD record
+        * `external_file_attributes: u32` The external file attributes of the CD record
+        * `directory_name: bool` The entry name ends with a slash
+        */
+        let host_system = HostSystem::from_version_made_by(version_made_by);
+        let high = external_file_attributes >> 16;
+        let unix_extension = external_file_attributes & DOS_UNIX_EXTENSION != 0;
+        let unix_mode = match (host_system.stores_unix_mode() || unix_extension) && high != 0 {
+            true => Some(UnixMode(high)),
+            false => None,
+        };
+
+        Self {
+            host_system,
+            unix_mode,
+            dos: DosAttributes(external_file_attributes as u8),
+            directory_name,
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        /*
+        The file type of the Unix mode, falling back to the DOS directory attribute
+        and the trailing slash of the name
+        */
+        if let Some(file_type) = self.unix_mode.and_then(|mode| mode.file_type()) {
+            return file_type;
+        }
+        match self.dos.is_directory() || self.directory_name {
+            true => FileType::Directory,
+            false => FileType::Regular,
+        }
+    }
+
+    pub fn mode_string(&self) -> String {
+        /*
+        The mode in the form ls -l shows it, such as drwxr-xr-x. Entries without a
+        Unix mode get the permissions a Unix unzip would give them: rw-r--r-- for
+        files and rwxr-xr-x for directories, without write access when read-only.
+        */
+        let type_character = match self.file_type() {
+            FileType::Regular => '-',
+            FileType::Directory => 'd',
+            FileType::Symlink => 'l',
+            FileType::Fifo => 'p',
+            FileType::CharacterDevice => 'c',
+            FileType::BlockDevice => 'b',
+            FileType::Socket => 's',
+        };
+
+        let mode = self.unix_mode.unwrap_or_else(|| {
+            let mut permissions = match self.file_type() {
+                FileType::Directory => 0o755,
+                _ => 0o644,
+            };
+            if self.dos.is_read_only() {
+                permissions &= !0o222;
+            }
+            UnixMode(permissions)
+        });
+        format!("{}{}", type_character, mode.permission_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIX: u16 = 3 << 8 | 30;
+    const MS_DOS: u16 = 20;
+
+    fn unix(mode: u32, directory_name: bool) -> FileAttributes {
+        FileAttributes::new(UNIX, mode << 16, directory_name)
+    }
+
+    #[test]
+    fn mode_string_of_a_regular_file() {
+        assert_eq!(unix(S_IFREG | 0o644, false).mode_string(), "-rw-r--r--");
+        assert_eq!(unix(S_IFREG | 0o750, false).mode_string(), "-rwxr-x---");
+    }
+
+    #[test]
+    fn mode_string_shows_setuid_setgid_and_sticky() {
+        assert_eq!(unix(S_IFREG | 0o4755, false).mode_string(), "-rwsr-xr-x");
+        assert_eq!(unix(S_IFREG | 0o4644, false).mode_string(), "-rwSr--r--");
+        assert_eq!(unix(S_IFREG | 0o2755, false).mode_string(), "-rwxr-sr-x");
+        assert_eq!(unix(S_IFREG | 0o2745, false).mode_string(), "-rwxr-Sr-x");
+        assert_eq!(unix(S_IFDIR | 0o1777, true).mode_string(), "drwxrwxrwt");
+        assert_eq!(unix(S_IFDIR | 0o1776, true).mode_string(), "drwxrwxrwT");
+    }
+
+    #[test]
+    fn symlink() {
+        let attributes = unix(S_IFLNK | 0o777, false);
+        assert_eq!(attributes.file_type(), FileType::Symlink);
+        assert_eq!(attributes.mode_string(), "lrwxrwxrwx");
+    }
+
+    #[test]
+    fn directory_from_the_unix_mode_without_a_trailing_slash() {
+        let attributes = unix(S_IFDIR | 0o755, false);
+        assert_eq!(attributes.file_type(), FileType::Directory);
+        assert_eq!(attributes.mode_string(), "drwxr-xr-x");
+    }
+
+    #[test]
+    fn dos_entries_get_default_permissions() {
+        assert_eq!(FileAttributes::new(MS_DOS, 0x20, false).mode_string(), "-rw-r--r--");
+        assert_eq!(FileAttributes::new(MS_DOS, 0x21, false).mode_string(), "-r--r--r--");
+        assert_eq!(FileAttributes::new(MS_DOS, 0x10, false).mode_string(), "drwxr-xr-x");
+        assert_eq!(FileAttributes::new(MS_DOS, 0, true).mode_string(), "drwxr-xr-x");
+    }
+
+    #[test]
+    fn dos_hosts_ignore_the_high_bits_without_the_unix_extension() {
+        let attributes = FileAttributes::new(MS_DOS, (S_IFLNK | 0o777) << 16 | 0x20, false);
+        assert_eq!(attributes.unix_mode, None);
+        assert_eq!(attributes.file_type(), FileType::Regular);
+
+        let attributes = FileAttributes::new(MS_DOS, (S_IFLNK | 0o777) << 16 | DOS_UNIX_EXTENSION, false);
+        assert_eq!(attributes.unix_mode, Some(UnixMode(S_IFLNK | 0o777)));
+        assert_eq!(attributes.file_type(), FileType::Symlink);
+    }
+
+    #[test]
+    fn dos_attributes_columns() {
+        assert_eq!(DosAttributes(0x00).to_string(), "-----");
+        assert_eq!(DosAttributes(0x37).to_string(), "RHSDA");
+    }
+}
@@ -1,5 +1,11 @@
 use bytes::{Buf, Bytes};
-use std::mem::size_of;
+
+use crate::attributes::{FileAttributes, HostSystem};
+use crate::compression::CompressionMethod;
+use crate::encoding::NameEncoding;
+use crate::extra::{ExtraField, ExtraFields, Zip64ExtendedInformation, Zip64Values};
+use crate::flags::GeneralPurposeFlags;
+use crate::timestamp::{EntryTimes, Timestamp};
 
 struct FieldMetaData {
     size: usize,
@@ -10,6 +16,14 @@ struct FieldMetaData {
 pub struct Cd {
     binary: Bytes,
     pub word_size: WordSize,
+    /*
+    The code page of the name and comment when general purpose bit 11 is clear.
+    */
+    pub name_encoding: NameEncoding,
+    /*
+    Bytes of data prepended to the archive, see `Eocd::base_offset`.
+    */
+    pub base_offset: u64,
 }
 
 #[derive(Debug, PartialEq)]
@@ -18,10 +32,50 @@ pub enum WordSize {
     Bit64,
 }
 
+/*
+Where the name or comment of an entry was taken from.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextSource {
+    Header,
+    /*
+    The Info-ZIP Unicode Path or Unicode Comment extra field.
+    */
+    UnicodeExtra,
+    /*
+    The header, as the Unicode extra field was written for a different name or
+    comment and has gone stale.
+    */
+    StaleUnicodeExtra,
+}
+
+impl std::fmt::Display for TextSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TextSource::Header => write!(f, "header"),
+            TextSource::UnicodeExtra => write!(f, "Unicode extra field"),
+            TextSource::StaleUnicodeExtra => write!(f, "header (stale Unicode extra field)"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CdError {
     NotValidBinary,
     InvalidUTF8ByteVector,
+    Truncated,
+}
+
+impl std::error::Error for CdError {}
+
+impl std::fmt::Display for CdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CdError::NotValidBinary => write!(f, "CD signature not found"),
+            CdError::InvalidUTF8ByteVector => write!(f, "Invalid UTF-8 byte vector"),
+            CdError::Truncated => write!(f, "CD record is truncated"),
+        }
+    }
 }
 
 impl FieldMetaData {
@@ -36,11 +90,25 @@ impl Cd {
     */
     const CD_32_CHECKSUM: &str = "02014b50";
 
+    /*
+    Size of the fixed portion of a Central Directory entry, before the
+    file name, extra field and file comment.
+    */
+    pub const HEADER_SIZE: usize = 46;
+
     pub fn from(binary: Bytes) -> Self {
-        Self {
-            word_size: Self::verify(&binary).unwrap(),
-            binary,
-        }
+        Self::try_from(binary).unwrap()
+    }
+
+    pub fn record_length(&self) -> usize {
+        /*
+        Description: Length of the whole entry
+        46 + n + m + k
+        */
+        Self::HEADER_SIZE
+            + self.file_name_length() as usize
+            + self.extra_field_length() as usize
+            + self.file_comment_length() as usize
     }
 
     fn verify(binary: &Bytes) -> Result<WordSize, CdError> {
@@ -51,7 +119,11 @@ impl Cd {
         */
         let metadata = FieldMetaData { offset: 0, size: 4 }.slice_range();
 
-        let first_four_bytes = binary.slice(0..size_of::<u32>());
+        if binary.len() < metadata.end {
+            return Err(CdError::Truncated);
+        }
+
+        let first_four_bytes = binary.slice(metadata);
         if first_four_bytes == Cd::cd_32_checksum() {
             return Ok(WordSize::Bit32);
         }
@@ -103,6 +175,14 @@ impl Cd {
         self.binary.slice(metadata).get_u16_le()
     }
 
+    pub fn flags(&self) -> GeneralPurposeFlags {
+        GeneralPurposeFlags::from_bits_retain(self.general_purpose_bit_flag())
+    }
+
+    pub fn compression(&self) -> CompressionMethod {
+        CompressionMethod::from(self.compression_method())
+    }
+
     pub fn file_last_modification_time(&self) -> u16 {
         /*
         Description: File last modification time
@@ -131,6 +211,22 @@ impl Cd {
         self.binary.slice(metadata).get_u16_le()
     }
 
+    pub fn times(&self) -> EntryTimes {
+        /*
+        The decoded modification, access and creation times, taken from the most
+        precise source available
+        */
+        EntryTimes::new(
+            self.file_last_modification_date(),
+            self.file_last_modification_time(),
+            self.extra_fields(),
+        )
+    }
+
+    pub fn last_modification(&self) -> Option<Timestamp> {
+        self.times().modified
+    }
+
     pub fn crc_32_of_uncompressed_data(&self) -> u32 {
         /*
         Description: CRC-32 of uncompressed data
@@ -145,87 +241,42 @@ impl Cd {
         self.binary.slice(metadata).get_u32_le()
     }
 
-    pub fn compressed_size(&mut self) -> u64 {
+    pub fn compressed_size(&self) -> u64 {
         /*
         Description: Compressed size (or 0xffffffff for ZIP64)
         32-bit offset: 20
         32-bit size: 4
-        64-bit offset:
-        */
-        let bit64_checksum: &[u8] = b"\xff\xff\xff\xff";
-        let mut metadata = FieldMetaData { offset: 0, size: 4 }.slice_range();
-        let checksum_bytes = self.binary.slice(metadata);
-
-        metadata = if checksum_bytes.as_ref() == bit64_checksum {
-            /*
-            On ZIP64, the uncompressed data field is in the CD Extra Field header.
-            Extra-Field offset: 12
-            Extra-Field size: 8
-            The Extra Field header is at the offset 46+n where n=File Name Length.
-            So to extract the uncompressed data field. The equation to calculate the field we need
-            46 + n + 12
-            */
-            self.word_size = WordSize::Bit64;
-            FieldMetaData {
-                offset: 58 + self.file_name_length() as usize,
-                size: 8,
-            }
-            .slice_range()
-        } else {
-            self.word_size = WordSize::Bit32;
-            FieldMetaData {
-                offset: 20,
-                size: 4,
-            }
-            .slice_range()
-        };
+        64-bit: ZIP64 extended information extra field
+        */
+        let metadata = FieldMetaData {
+            offset: 20,
+            size: 4,
+        }
+        .slice_range();
+        let compressed_size = self.binary.slice(metadata).get_u32_le();
 
-        let mut bytes = self.binary.slice(metadata);
-        return match self.word_size {
-            WordSize::Bit32 => bytes.get_u32_le() as u64,
-            WordSize::Bit64 => bytes.get_u64_le(),
-        };
+        self.zip64_values()
+            .compressed_size
+            .unwrap_or(compressed_size as u64)
     }
 
-    pub fn uncompressed_size(&mut self) -> u64 {
+    pub fn uncompressed_size(&self) -> u64 {
         /*
         Description: Unompressed size (or 0xffffffff for ZIP64)
         32-bit offset: 24
         32-bit size: 4
+        64-bit: ZIP64 extended information extra field
         */
-        let bit64_checksum: &[u8] = b"\xff\xff\xff\xff";
-        let mut metadata = FieldMetaData { offset: 0, size: 4 }.slice_range();
-        let checksum_bytes = self.binary.slice(metadata);
-
-        metadata = if checksum_bytes.as_ref() == bit64_checksum {
-            /*
-            On ZIP64, the uncompressed data field is in the CD Extra Field header.
-            Extra-Field offset: 4
-            Extra-Field size: 8
-            The Extra Field header is at the offset 46+n where n=File Name Length.
-            So to extract the uncompressed data field. The equation to calculate the field we need
-            46 + n + 4
-            */
-            self.word_size = WordSize::Bit64;
-            FieldMetaData {
-                offset: 50 + self.file_name_length() as usize,
-                size: 8,
-            }
-            .slice_range()
-        } else {
-            self.word_size = WordSize::Bit32;
-            FieldMetaData {
-                offset: 24,
-                size: 4,
-            }
-            .slice_range()
-        };
+        let metadata = FieldMetaData {
+            offset: 24,
+            size: 4,
+        }
+        .slice_range();
+        let uncompressed_size = self.binary.slice(metadata).get_u32_le();
 
-        let mut bytes = self.binary.slice(metadata);
-        return match self.word_size {
-            WordSize::Bit32 => bytes.get_u32_le() as u64,
-            WordSize::Bit64 => bytes.get_u64_le(),
-        };
+        self.zip64_values()
+            .uncompressed_size
+            .unwrap_or(uncompressed_size as u64)
     }
 
     pub fn file_name_length(&self) -> u16 {
@@ -270,46 +321,23 @@ impl Cd {
         self.binary.slice(metadata).get_u16_le()
     }
 
-    pub fn disk_where_file_starts(&mut self) -> u32 {
+    pub fn disk_where_file_starts(&self) -> u32 {
         /*
         Description: Disk number where file starts (or 0xffff for ZIP64)
         32-bit offset: 34
         32-bit size: 2
+        64-bit: ZIP64 extended information extra field
         */
+        let metadata = FieldMetaData {
+            offset: 34,
+            size: 2,
+        }
+        .slice_range();
+        let disk_where_file_starts = self.binary.slice(metadata).get_u16_le();
 
-        let bit64_checksum: &[u8] = b"\xff\xff";
-        let mut metadata = FieldMetaData { offset: 0, size: 4 }.slice_range();
-        let checksum_bytes = self.binary.slice(metadata);
-
-        metadata = if checksum_bytes.as_ref() == bit64_checksum {
-            /*
-            On ZIP64, the disk where file starts field is in the CD Extra Field header.
-            Extra-Field offset: 28
-            Extra-Field size: 4
-            The Extra Field header is at the offset 46+n where n=File Name Length.
-            So to extract the uncompressed data field. The equation to calculate the field we need
-            46 + n + 28
-            */
-            self.word_size = WordSize::Bit64;
-            FieldMetaData {
-                offset: 74 + self.file_name_length() as usize,
-                size: 4,
-            }
-            .slice_range()
-        } else {
-            self.word_size = WordSize::Bit32;
-            FieldMetaData {
-                offset: 34,
-                size: 2,
-            }
-            .slice_range()
-        };
-
-        let mut bytes = self.binary.slice(metadata);
-        return match self.word_size {
-            WordSize::Bit32 => bytes.get_u16_le() as u32,
-            WordSize::Bit64 => bytes.get_u32_le(),
-        };
+        self.zip64_values()
+            .disk_start_number
+            .unwrap_or(disk_where_file_starts as u32)
     }
 
     pub fn internal_file_attributes(&self) -> u16 {
@@ -340,7 +368,46 @@ impl Cd {
         self.binary.slice(metadata).get_u32_le()
     }
 
-    pub fn file_name(&self) -> Result<String, CdError> {
+    pub fn host_system(&self) -> HostSystem {
+        /*
+        The system the entry was written on, the high byte of version made by
+        */
+        HostSystem::from_version_made_by(self.version_made_by())
+    }
+
+    pub fn file_attributes(&self) -> FileAttributes {
+        /*
+        The external file attributes, interpreted for the host system. Names ending
+        in a backslash, as some Windows archivers write them, mark a directory too.
+        */
+        let name = self.file_name_raw();
+        FileAttributes::new(
+            self.version_made_by(),
+            self.external_file_attributes(),
+            name.ends_with(b"/") || name.ends_with(b"\\"),
+        )
+    }
+
+    pub fn relative_offset_of_local_file_header(&self) -> u64 {
+        /*
+        Description: Relative offset of local file header (or 0xffffffff for ZIP64)
+        32-bit offset: 42
+        32-bit size: 4
+        64-bit: ZIP64 extended information extra field
+        */
+        let metadata = FieldMetaData {
+            offset: 42,
+            size: 4,
+        }
+        .slice_range();
+        let relative_offset = self.binary.slice(metadata).get_u32_le();
+
+        self.zip64_values()
+            .local_header_offset
+            .unwrap_or(relative_offset as u64)
+    }
+
+    pub fn file_name_raw(&self) -> Bytes {
         /*
         Description: File Name
         32-bit offset: 46
@@ -351,13 +418,162 @@ impl Cd {
             size: self.file_name_length() as usize,
         }
         .slice_range();
-        let utf8_bytes = self.binary.slice(metadata).to_vec();
-        match String::from_utf8(utf8_bytes) {
-            Ok(file_name) => Ok(file_name),
-            Err(_) => Err(CdError::InvalidUTF8ByteVector),
+        self.binary.slice(metadata)
+    }
+
+    pub fn file_name(&self) -> Result<String, CdError> {
+        /*
+        The UTF-8 name held by the Unicode Path extra field when it matches the header
+        name, otherwise the header name itself. The header name is UTF-8 when general
+        purpose bit 11 is set and in the name_encoding code page when it is not.
+        */
+        match self.unicode_text(&self.file_name_raw(), false) {
+            (Some(file_name), _) => Ok(file_name),
+            (None, _) => self.decode_text(&self.file_name_raw()),
+        }
+    }
+
+    pub fn file_name_hex(&self) -> String {
+        /*
+        The raw bytes of the header name, hex encoded
+        */
+        hex::encode(self.file_name_raw())
+    }
+
+    pub fn file_name_lossy(&self) -> String {
+        /*
+        Same as file_name, with the bytes which are not valid UTF-8 replaced
+        */
+        self.file_name()
+            .unwrap_or_else(|_| String::from_utf8_lossy(&self.file_name_raw()).into_owned())
+    }
+
+    pub fn file_name_source(&self) -> TextSource {
+        self.unicode_text(&self.file_name_raw(), false).1
+    }
+
+    pub fn file_comment_raw(&self) -> Bytes {
+        /*
+        Description: File comment
+        32-bit offset: 46 + n + m
+        32-bit size: k
+        */
+        let metadata = FieldMetaData {
+            offset: Self::HEADER_SIZE + self.file_name_length() as usize + self.extra_field_length() as usize,
+            size: self.file_comment_length() as usize,
+        }
+        .slice_range();
+        self.binary.slice(metadata)
+    }
+
+    pub fn file_comment(&self) -> Result<String, CdError> {
+        /*
+        The UTF-8 comment held by the Unicode Comment extra field when it matches the
+        header comment, otherwise the header comment itself, decoded like the name.
+        */
+        match self.unicode_text(&self.file_comment_raw(), true) {
+            (Some(file_comment), _) => Ok(file_comment),
+            (None, _) => self.decode_text(&self.file_comment_raw()),
+        }
+    }
+
+    pub fn file_comment_lossy(&self) -> String {
+        /*
+        Same as file_comment, with the bytes which are not valid UTF-8 replaced
+        */
+        self.file_comment()
+            .unwrap_or_else(|_| String::from_utf8_lossy(&self.file_comment_raw()).into_owned())
+    }
+
+    pub fn file_comment_source(&self) -> TextSource {
+        self.unicode_text(&self.file_comment_raw(), true).1
+    }
+
+    pub fn extra_field(&self) -> Bytes {
+        /*
+        Description: Extra field
+        32-bit offset: 46 + n
+        32-bit size: m
+        */
+        let metadata = FieldMetaData {
+            offset: Self::HEADER_SIZE + self.file_name_length() as usize,
+            size: self.extra_field_length() as usize,
+        }
+        .slice_range();
+        self.binary.slice(metadata)
+    }
+
+    pub fn extra_fields(&self) -> ExtraFields {
+        /*
+        Iterates over the typed records held within the extra field
+        */
+        ExtraFields::new(self.extra_field())
+    }
+
+    fn decode_text(&self, raw: &Bytes) -> Result<String, CdError> {
+        match self.flags().contains(GeneralPurposeFlags::UTF8) {
+            true => String::from_utf8(raw.to_vec()).map_err(|_| CdError::InvalidUTF8ByteVector),
+            false => Ok(self.name_encoding.decode(raw)),
+        }
+    }
+
+    fn unicode_text(&self, raw: &Bytes, comment: bool) -> (Option<String>, TextSource) {
+        /*
+        Looks for the Unicode Path (or Unicode Comment) extra field of this entry.
+        Its text is only used when it is version 1, valid UTF-8 and its CRC-32
+        matches the raw header bytes, otherwise the header was edited by a tool
+        unaware of the extra field.
+
+        # Arguments
+        * `raw: &Bytes` The raw name or comment from the header
+        * `comment: bool` Looks for the Unicode Comment instead of the Unicode Path
+        */
+        let unicode = self.extra_fields().find_map(|field| match (field, comment) {
+            (ExtraField::UnicodePath(unicode), false) => Some(unicode),
+            (ExtraField::UnicodeComment(unicode), true) => Some(unicode),
+            _ => None,
+        });
+
+        match unicode {
+            Some(unicode) if unicode.version == 1 && unicode.crc_32 == crc32fast::hash(raw) => {
+                match String::from_utf8(unicode.data.to_vec()) {
+                    Ok(text) => (Some(text), TextSource::UnicodeExtra),
+                    Err(_) => (None, TextSource::StaleUnicodeExtra),
+                }
+            }
+            Some(_) => (None, TextSource::StaleUnicodeExtra),
+            None => (None, TextSource::Header),
         }
     }
 
+    fn zip64_extended_information(&self) -> Option<Zip64ExtendedInformation> {
+        self.extra_fields().find_map(|field| match field {
+            ExtraField::Zip64ExtendedInformation(zip64) => Some(zip64),
+            _ => None,
+        })
+    }
+
+    fn zip64_values(&self) -> Zip64Values {
+        /*
+        Resolves the ZIP64 values for the fields of this entry which are saturated.
+        The ZIP64 extended information only holds those fields, in a fixed order.
+        */
+        let zip64 = match self.zip64_extended_information() {
+            Some(zip64) => zip64,
+            None => return Zip64Values::default(),
+        };
+
+        let saturated_u32 = |offset: usize| self.binary.slice(offset..offset + 4).get_u32_le() == 0xFFFFFFFF;
+        let saturated_u16 = |offset: usize| self.binary.slice(offset..offset + 2).get_u16_le() == 0xFFFF;
+
+        zip64.resolve(
+            saturated_u32(24),
+            saturated_u32(20),
+            saturated_u32(42),
+            saturated_u16(34),
+        )
+    }
+
     fn cd_32_checksum() -> Bytes {
         let byte_vector = hex::decode(Self::CD_32_CHECKSUM).expect("Invalid Hex String");
         let big_endian_bytes = Bytes::from(byte_vector);
@@ -381,3 +597,35 @@ impl Cd {
         Bytes::from(little_endian_bytes)
     }
 }
+
+impl TryFrom<Bytes> for Cd {
+    type Error = CdError;
+
+    fn try_from(binary: Bytes) -> Result<Self, Self::Error> {
+        /*
+        Builds an entry from the start of the supplied buffer. The buffer may run past
+        the end of the entry, the surplus is sliced off using the variable field lengths.
+        */
+        let word_size = Self::verify(&binary)?;
+        if binary.len() < Self::HEADER_SIZE {
+            return Err(CdError::Truncated);
+        }
+
+        let mut cd = Self {
+            binary,
+            word_size,
+            name_encoding: NameEncoding::default(),
+            base_offset: 0,
+        };
+        let record_length = cd.record_length();
+        if cd.binary.len() < record_length {
+            return Err(CdError::Truncated);
+        }
+        cd.binary.truncate(record_length);
+
+        if cd.zip64_extended_information().is_some() {
+            cd.word_size = WordSize::Bit64;
+        }
+        Ok(cd)
+    }
+}
@@ -0,0 +1,139 @@
+/*
+The compression method of an entry, every ID registered by APPNOTE 4.4.5.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressionMethod {
+    Stored,
+    Shrunk,
+    Reduced1,
+    Reduced2,
+    Reduced3,
+    Reduced4,
+    Imploded,
+    Tokenized,
+    Deflated,
+    Deflate64,
+    PkwareImploded,
+    Bzip2,
+    Lzma,
+    IbmCmpsc,
+    IbmTerse,
+    IbmLz77,
+    /*
+    The ID zstd used before 93 was assigned to it.
+    */
+    ZstandardDeprecated,
+    Zstandard,
+    Mp3,
+    Xz,
+    Jpeg,
+    WavPack,
+    Ppmd,
+    /*
+    Marks a WinZip AES encrypted entry, the real method is in the AES extra field.
+    */
+    Aes,
+    Unknown(u16),
+}
+
+impl CompressionMethod {
+    pub fn id(&self) -> u16 {
+        match self {
+            CompressionMethod::Stored => 0,
+            CompressionMethod::Shrunk => 1,
+            CompressionMethod::Reduced1 => 2,
+            CompressionMethod::Reduced2 => 3,
+            CompressionMethod::Reduced3 => 4,
+            CompressionMethod::Reduced4 => 5,
+            CompressionMethod::Imploded => 6,
+            CompressionMethod::Tokenized => 7,
+            CompressionMethod::Deflated => 8,
+            CompressionMethod::Deflate64 => 9,
+            CompressionMethod::PkwareImploded => 10,
+            CompressionMethod::Bzip2 => 12,
+            CompressionMethod::Lzma => 14,
+            CompressionMethod::IbmCmpsc => 16,
+            CompressionMethod::IbmTerse => 18,
+            CompressionMethod::IbmLz77 => 19,
+            CompressionMethod::ZstandardDeprecated => 20,
+            CompressionMethod::Zstandard => 93,
+            CompressionMethod::Mp3 => 94,
+            CompressionMethod::Xz => 95,
+            CompressionMethod::Jpeg => 96,
+            CompressionMethod::WavPack => 97,
+            CompressionMethod::Ppmd => 98,
+            CompressionMethod::Aes => 99,
+            CompressionMethod::Unknown(id) => *id,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionMethod::Stored => "store",
+            CompressionMethod::Shrunk => "shrink",
+            CompressionMethod::Reduced1 => "reduce1",
+            CompressionMethod::Reduced2 => "reduce2",
+            CompressionMethod::Reduced3 => "reduce3",
+            CompressionMethod::Reduced4 => "reduce4",
+            CompressionMethod::Imploded => "implode",
+            CompressionMethod::Tokenized => "tokenize",
+            CompressionMethod::Deflated => "deflate",
+            CompressionMethod::Deflate64 => "deflate64",
+            CompressionMethod::PkwareImploded => "pkware-dcl",
+            CompressionMethod::Bzip2 => "bzip2",
+            CompressionMethod::Lzma => "lzma",
+            CompressionMethod::IbmCmpsc => "ibm-cmpsc",
+            CompressionMethod::IbmTerse => "ibm-terse",
+            CompressionMethod::IbmLz77 => "ibm-lz77",
+            CompressionMethod::ZstandardDeprecated | CompressionMethod::Zstandard => "zstd",
+            CompressionMethod::Mp3 => "mp3",
+            CompressionMethod::Xz => "xz",
+            CompressionMethod::Jpeg => "jpeg",
+            CompressionMethod::WavPack => "wavpack",
+            CompressionMethod::Ppmd => "ppmd",
+            CompressionMethod::Aes => "aes",
+            CompressionMethod::Unknown(_) => "unknown",
+        }
+    }
+}
+
+impl From<u16> for CompressionMethod {
+    fn from(id: u16) -> Self {
+        match id {
+            0 => CompressionMethod::Stored,
+            1 => CompressionMethod::Shrunk,
+            2 => CompressionMethod::Reduced1,
+            3 => CompressionMethod::Reduced2,
+            4 => CompressionMethod::Reduced3,
+            5 => CompressionMethod::Reduced4,
+            6 => CompressionMethod::Imploded,
+            7 => CompressionMethod::Tokenized,
+            8 => CompressionMethod::Deflated,
+            9 => CompressionMethod::Deflate64,
+            10 => CompressionMethod::PkwareImploded,
+            12 => CompressionMethod::Bzip2,
+            14 => CompressionMethod::Lzma,
+            16 => CompressionMethod::IbmCmpsc,
+            18 => CompressionMethod::IbmTerse,
+            19 => CompressionMethod::IbmLz77,
+            20 => CompressionMethod::ZstandardDeprecated,
+            93 => CompressionMethod::Zstandard,
+            94 => CompressionMethod::Mp3,
+            95 => CompressionMethod::Xz,
+            96 => CompressionMethod::Jpeg,
+            97 => CompressionMethod::WavPack,
+            98 => CompressionMethod::Ppmd,
+            99 => CompressionMethod::Aes,
+            id => CompressionMethod::Unknown(id),
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CompressionMethod::Unknown(id) => write!(f, "unknown ({})", id),
+            method => write!(f, "{}", method.name()),
+        }
+    }
+}
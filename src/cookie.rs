@@ -0,0 +1,269 @@
+use reqwest::cookie::CookieStore;
+use reqwest::header::HeaderValue;
+use reqwest::Url;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+Prefix curl puts in front of the domain of cookies flagged HttpOnly, which would
+otherwise read as a comment
+*/
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/*
+A cookie of a Netscape cookie jar, the cookies.txt format of curl, wget and
+browser export extensions.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct JarCookie {
+    pub domain: String,
+    /*
+    The cookie is sent to the subdomains of `domain` as well.
+    */
+    pub include_subdomains: bool,
+    pub path: String,
+    /*
+    The cookie is only sent over HTTPS.
+    */
+    pub secure: bool,
+    /*
+    Expiry as a Unix timestamp, 0 for a session cookie.
+    */
+    pub expires: u64,
+    pub name: String,
+    pub value: String,
+}
+
+/*
+The cookies sent along with the requests of a run. The cookies given by name
+and value go with every request, the cookies of a jar only with the requests
+they match. Cookies set by the server are not kept.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    pub cookies: Vec<(String, String)>,
+    pub jar: Vec<JarCookie>,
+}
+
+#[derive(Debug)]
+pub enum CookieError {
+    MalformedLine(usize),
+}
+
+impl std::error::Error for CookieError {}
+
+impl std::fmt::Display for CookieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CookieError::MalformedLine(line) => {
+                write!(f, "Line {} of the cookie jar is not in the Netscape format", line)
+            }
+        }
+    }
+}
+
+impl JarCookie {
+    pub fn parse_jar(text: &str) -> Result<Vec<Self>, CookieError> {
+        /*
+        One cookie per line, seven fields separated by tabs:
+        domain  include_subdomains  path  secure  expires  name  value
+        Empty lines and lines starting with # are skipped, except for the #HttpOnly_
+        prefix.
+
+        # Arguments
+        * `text: &str` The content of the cookie jar
+        */
+        let mut cookies = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            let line = line.strip_prefix(HTTP_ONLY_PREFIX).unwrap_or(line);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let malformed = || CookieError::MalformedLine(index + 1);
+            if fields.len() != 7 {
+                return Err(malformed());
+            }
+            cookies.push(Self {
+                domain: fields[0].to_ascii_lowercase(),
+                include_subdomains: fields[1].eq_ignore_ascii_case("TRUE"),
+                path: fields[2].to_string(),
+                secure: fields[3].eq_ignore_ascii_case("TRUE"),
+                expires: fields[4].parse().map_err(|_| malformed())?,
+                name: fields[5].to_string(),
+                value: fields[6].to_string(),
+            });
+        }
+        Ok(cookies)
+    }
+
+    pub fn matches(&self, url: &Url, now: u64) -> bool {
+        /*
+        Whether a browser would send the cookie along with a request for <url>
+        at the Unix time <now>
+
+        # Arguments
+        * `url: &Url` The URL requested
+        * `now: u64` The current Unix time
+        */
+        let Some(host) = url.host_str().map(|host| host.to_ascii_lowercase()) else {
+            return false;
+        };
+        let domain = self.domain.trim_start_matches('.');
+        let domain_matches = host == domain
+            || (self.include_subdomains && host.ends_with(&format!(".{}", domain)));
+        let path_matches = self.path_matches(url.path());
+        let scheme_matches = !self.secure || url.scheme() == "https";
+        let alive = self.expires == 0 || self.expires > now;
+
+        domain_matches && path_matches && scheme_matches && alive
+    }
+
+    fn path_matches(&self, request_path: &str) -> bool {
+        /*
+        RFC 6265 5.1.4: the paths are equal, or the cookie path is a prefix of the
+        request path which ends with a slash or is followed by one, so /foo matches
+        /foo/bar but not /foobar
+        */
+        match request_path.strip_prefix(self.path.as_str()) {
+            Some(rest) => rest.is_empty() || self.path.ends_with('/') || rest.starts_with('/'),
+            None => false,
+        }
+    }
+}
+
+impl CookieJar {
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty() && self.jar.is_empty()
+    }
+
+    pub fn header_value(&self, url: &Url, now: u64) -> Option<String> {
+        /*
+        The value of the Cookie header of a request for <url> at the Unix time <now>,
+        None when no cookie goes along with it
+
+        # Arguments
+        * `url: &Url` The URL requested
+        * `now: u64` The current Unix time
+        */
+        let matching = self
+            .jar
+            .iter()
+            .filter(|cookie| cookie.matches(url, now))
+            .map(|cookie| (&cookie.name, &cookie.value));
+        let cookies: Vec<String> = self
+            .cookies
+            .iter()
+            .map(|(name, value)| (name, value))
+            .chain(matching)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        match cookies.is_empty() {
+            true => None,
+            false => Some(cookies.join("; ")),
+        }
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, _cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, _url: &Url) {}
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let mut value = HeaderValue::from_str(&self.header_value(url, now)?).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JAR: &str = "# Netscape HTTP Cookie File\n\
+        # a comment\n\
+        \n\
+        .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+        #HttpOnly_files.example.com\tFALSE\t/downloads\tTRUE\t2000000000\ttoken\txyz\r\n\
+        example.org\tFALSE\t/\tFALSE\t1000\texpired\told\n";
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn parse_jar_reads_cookies_and_skips_comments() {
+        let jar = JarCookie::parse_jar(JAR).unwrap();
+        assert_eq!(jar.len(), 3);
+        assert_eq!(
+            jar[1],
+            JarCookie {
+                domain: String::from("files.example.com"),
+                include_subdomains: false,
+                path: String::from("/downloads"),
+                secure: true,
+                expires: 2000000000,
+                name: String::from("token"),
+                value: String::from("xyz"),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_jar_rejects_malformed_lines() {
+        assert!(matches!(
+            JarCookie::parse_jar("# header\nexample.com\tTRUE\t/\n"),
+            Err(CookieError::MalformedLine(2))
+        ));
+        assert!(matches!(
+            JarCookie::parse_jar("example.com\tTRUE\t/\tFALSE\tnever\tname\tvalue\n"),
+            Err(CookieError::MalformedLine(1))
+        ));
+    }
+
+    #[test]
+    fn expired_cookies_do_not_match() {
+        let jar = JarCookie::parse_jar(JAR).unwrap();
+        assert!(jar[2].matches(&url("http://example.org/a.zip"), 999));
+        assert!(!jar[2].matches(&url("http://example.org/a.zip"), 1000));
+        assert!(jar[0].matches(&url("http://example.com/a.zip"), u64::MAX));
+    }
+
+    #[test]
+    fn domain_and_scheme_matching() {
+        let jar = JarCookie::parse_jar(JAR).unwrap();
+        assert!(jar[0].matches(&url("http://cdn.example.com/a.zip"), 0));
+        assert!(!jar[0].matches(&url("http://badexample.com/a.zip"), 0));
+        assert!(!jar[1].matches(&url("https://sub.files.example.com/downloads/a.zip"), 0));
+        assert!(!jar[1].matches(&url("http://files.example.com/downloads/a.zip"), 0));
+    }
+
+    #[test]
+    fn path_matching_follows_rfc_6265() {
+        let jar = JarCookie::parse_jar(JAR).unwrap();
+        let token = &jar[1];
+        assert!(token.matches(&url("https://files.example.com/downloads"), 0));
+        assert!(token.matches(&url("https://files.example.com/downloads/a.zip"), 0));
+        assert!(!token.matches(&url("https://files.example.com/downloadsfoo/a.zip"), 0));
+        assert!(!token.matches(&url("https://files.example.com/"), 0));
+    }
+
+    #[test]
+    fn cookie_jar_header_value_per_url() {
+        let jar = CookieJar {
+            cookies: vec![(String::from("a"), String::from("1"))],
+            jar: JarCookie::parse_jar(JAR).unwrap(),
+        };
+        assert_eq!(
+            jar.header_value(&url("http://www.example.com/a.zip"), 0).as_deref(),
+            Some("a=1; session=abc")
+        );
+        assert_eq!(jar.header_value(&url("http://other.net/a.zip"), 0).as_deref(), Some("a=1"));
+        assert_eq!(CookieJar::default().header_value(&url("http://other.net/"), 0), None);
+    }
+}
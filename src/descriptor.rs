@@ -0,0 +1,280 @@
+use bytes::{Buf, Bytes};
+
+use crate::cd::{Cd, WordSize};
+
+struct FieldMetaData {
+    size: usize,
+    offset: usize,
+}
+
+/*
+The data descriptor written right after the compressed data of an entry when
+general purpose bit 3 is set, as the CRC-32 and sizes were not known yet when
+the local file header was written.
+*/
+#[derive(Debug)]
+pub struct DataDescriptor {
+    binary: Bytes,
+    /*
+    The descriptor starts with the optional PK\x07\x08 signature.
+    */
+    pub signed: bool,
+    /*
+    Bit64 when the sizes are 8 bytes wide.
+    */
+    pub word_size: WordSize,
+}
+
+impl FieldMetaData {
+    fn slice_range(&self) -> std::ops::Range<usize> {
+        self.offset..(self.offset + self.size)
+    }
+}
+
+impl DataDescriptor {
+    /*
+    This checksum may signify the start of a data descriptor, writers are free
+    to leave it out.
+    */
+    const DATA_DESCRIPTOR_CHECKSUM: &'static [u8] = &[0x50, 0x4b, 0x07, 0x08];
+
+    /*
+    Size of the largest layout, a signed descriptor with 64-bit sizes.
+    */
+    pub const MAX_SIZE: usize = 24;
+
+    pub fn parse(binary: Bytes, zip64: bool, cd: &Cd) -> Option<Self> {
+        /*
+        Reads the data descriptor at the start of the supplied buffer.
+
+        Nothing in the descriptor tells its layout, it may or may not be signed and
+        its sizes are 4 or 8 bytes wide. The layouts are tried from the most likely
+        one, going by the signature and whether the local file header carries ZIP64
+        extended information. The first layout holding the CRC-32 and sizes of the CD
+        record wins, if none of them does the most likely layout is returned so the
+        disagreement can be reported.
+
+        # Arguments
+        * `binary: Bytes` The bytes right after the compressed data, up to MAX_SIZE
+        * `zip64: bool` The local file header carries ZIP64 extended information
+        * `cd: &Cd` The CD record of the entry
+        */
+        let signed = binary.starts_with(Self::DATA_DESCRIPTOR_CHECKSUM);
+        let word_size = |bit64: bool| match bit64 {
+            true => WordSize::Bit64,
+            false => WordSize::Bit32,
+        };
+        /*
+        A descriptor without a signature could still start with a CRC-32 that
+        happens to look like one
+        */
+        let mut layouts = vec![(signed, zip64), (signed, !zip64)];
+        if signed {
+            layouts.extend([(false, zip64), (false, !zip64)]);
+        }
+
+        let candidates: Vec<Self> = layouts
+            .into_iter()
+            .map(|(signed, bit64)| Self {
+                binary: binary.clone(),
+                signed,
+                word_size: word_size(bit64),
+            })
+            .filter(|descriptor| descriptor.length() <= binary.len())
+            .map(|mut descriptor| {
+                descriptor.binary.truncate(descriptor.length());
+                descriptor
+            })
+            .collect();
+
+        let matching = candidates.iter().position(|descriptor| {
+            descriptor.crc_32_of_uncompressed_data() == cd.crc_32_of_uncompressed_data()
+                && descriptor.compressed_size() == cd.compressed_size()
+                && descriptor.uncompressed_size() == cd.uncompressed_size()
+        });
+        candidates.into_iter().nth(matching.unwrap_or(0))
+    }
+
+    pub fn length(&self) -> usize {
+        /*
+        Description: Length of the whole descriptor
+        (4) + 4 + 4 + 4 or (4) + 4 + 8 + 8
+        */
+        self.fields_offset() + 4 + 2 * self.size_width()
+    }
+
+    pub fn crc_32_of_uncompressed_data(&self) -> u32 {
+        /*
+        Description: CRC-32 of uncompressed data
+        Offset: 0 or 4
+        Size: 4
+        */
+        let metadata = FieldMetaData {
+            offset: self.fields_offset(),
+            size: 4,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u32_le()
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        /*
+        Description: Compressed size
+        Offset: 4 or 8
+        Size: 4 or 8
+        */
+        self.size_field(self.fields_offset() + 4)
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        /*
+        Description: Uncompressed size
+        Offset: 8, 12 or 16
+        Size: 4 or 8
+        */
+        self.size_field(self.fields_offset() + 4 + self.size_width())
+    }
+
+    fn fields_offset(&self) -> usize {
+        match self.signed {
+            true => Self::DATA_DESCRIPTOR_CHECKSUM.len(),
+            false => 0,
+        }
+    }
+
+    fn size_width(&self) -> usize {
+        match self.word_size {
+            WordSize::Bit32 => 4,
+            WordSize::Bit64 => 8,
+        }
+    }
+
+    fn size_field(&self, offset: usize) -> u64 {
+        let metadata = FieldMetaData {
+            offset,
+            size: self.size_width(),
+        }
+        .slice_range();
+        match self.word_size {
+            WordSize::Bit32 => self.binary.slice(metadata).get_u32_le() as u64,
+            WordSize::Bit64 => self.binary.slice(metadata).get_u64_le(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURE: u32 = 0x08074b50;
+
+    fn cd(crc_32: u32, compressed_size: u32, uncompressed_size: u32) -> Cd {
+        let mut binary = vec![0x50, 0x4b, 0x01, 0x02, 20, 3, 20, 0, 0x08, 0, 8, 0, 0, 0, 0, 0];
+        binary.extend_from_slice(&crc_32.to_le_bytes());
+        binary.extend_from_slice(&compressed_size.to_le_bytes());
+        binary.extend_from_slice(&uncompressed_size.to_le_bytes());
+        binary.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        binary.push(b'a');
+        Cd::from(Bytes::from(binary))
+    }
+
+    fn descriptor(signed: bool, bit64: bool, crc_32: u32, compressed_size: u64, uncompressed_size: u64) -> Vec<u8> {
+        let mut binary = Vec::new();
+        if signed {
+            binary.extend_from_slice(&SIGNATURE.to_le_bytes());
+        }
+        binary.extend_from_slice(&crc_32.to_le_bytes());
+        match bit64 {
+            true => {
+                binary.extend_from_slice(&compressed_size.to_le_bytes());
+                binary.extend_from_slice(&uncompressed_size.to_le_bytes());
+            }
+            false => {
+                binary.extend_from_slice(&(compressed_size as u32).to_le_bytes());
+                binary.extend_from_slice(&(uncompressed_size as u32).to_le_bytes());
+            }
+        }
+        binary
+    }
+
+    fn parse(binary: Vec<u8>, zip64: bool, cd: &Cd) -> DataDescriptor {
+        /*
+        The descriptor is followed by the next local file header, as in an archive
+        */
+        let mut binary = binary;
+        binary.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        binary.truncate(DataDescriptor::MAX_SIZE);
+        DataDescriptor::parse(Bytes::from(binary), zip64, cd).unwrap()
+    }
+
+    fn assert_layout(descriptor: &DataDescriptor, signed: bool, word_size: WordSize, length: usize) {
+        assert_eq!(descriptor.signed, signed);
+        assert_eq!(descriptor.word_size, word_size);
+        assert_eq!(descriptor.length(), length);
+        assert_eq!(descriptor.crc_32_of_uncompressed_data(), 0x12345678);
+        assert_eq!(descriptor.compressed_size(), 100);
+        assert_eq!(descriptor.uncompressed_size(), 200);
+    }
+
+    #[test]
+    fn signed_32_bit() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(true, false, 0x12345678, 100, 200), false, &cd);
+        assert_layout(&descriptor, true, WordSize::Bit32, 16);
+    }
+
+    #[test]
+    fn unsigned_32_bit() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(false, false, 0x12345678, 100, 200), false, &cd);
+        assert_layout(&descriptor, false, WordSize::Bit32, 12);
+    }
+
+    #[test]
+    fn signed_64_bit() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(true, true, 0x12345678, 100, 200), true, &cd);
+        assert_layout(&descriptor, true, WordSize::Bit64, 24);
+    }
+
+    #[test]
+    fn unsigned_64_bit() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(false, true, 0x12345678, 100, 200), true, &cd);
+        assert_layout(&descriptor, false, WordSize::Bit64, 20);
+    }
+
+    #[test]
+    fn word_size_follows_the_cd_rather_than_the_zip64_hint() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(true, false, 0x12345678, 100, 200), true, &cd);
+        assert_layout(&descriptor, true, WordSize::Bit32, 16);
+    }
+
+    #[test]
+    fn unsigned_crc_looking_like_the_signature() {
+        let cd = cd(SIGNATURE, 100, 200);
+        let descriptor = parse(descriptor(false, false, SIGNATURE, 100, 200), false, &cd);
+        assert!(!descriptor.signed);
+        assert_eq!(descriptor.word_size, WordSize::Bit32);
+        assert_eq!(descriptor.length(), 12);
+        assert_eq!(descriptor.crc_32_of_uncompressed_data(), SIGNATURE);
+        assert_eq!(descriptor.compressed_size(), 100);
+        assert_eq!(descriptor.uncompressed_size(), 200);
+    }
+
+    #[test]
+    fn mismatch_falls_back_to_the_likeliest_layout() {
+        let cd = cd(0x12345678, 100, 200);
+        let descriptor = parse(descriptor(true, false, 0xdeadbeef, 100, 200), false, &cd);
+        assert!(descriptor.signed);
+        assert_eq!(descriptor.word_size, WordSize::Bit32);
+        assert_eq!(descriptor.crc_32_of_uncompressed_data(), 0xdeadbeef);
+    }
+
+    #[test]
+    fn too_short_for_any_layout() {
+        let cd = cd(0x12345678, 100, 200);
+        assert!(DataDescriptor::parse(Bytes::from_static(&[1, 2, 3]), false, &cd).is_none());
+    }
+}
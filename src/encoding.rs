@@ -0,0 +1,191 @@
+use encoding_rs::{Encoding, EUC_KR, GBK, IBM866, SHIFT_JIS, WINDOWS_1252};
+
+/*
+The upper half of IBM code page 437, the lower half is plain ASCII
+*/
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/*
+The code page names and comments are decoded with when the language encoding
+flag (general purpose bit 11) is clear. APPNOTE names IBM code page 437, but
+archivers commonly write the OEM code page of the system they run on.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameEncoding {
+    #[default]
+    Cp437,
+    ShiftJis,
+    Gbk,
+    EucKr,
+    Cp866,
+    Cp1252,
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    UnknownEncoding(String),
+}
+
+impl std::error::Error for EncodingError {}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncodingError::UnknownEncoding(name) => write!(
+                f,
+                "Unknown name encoding {} (expected cp437, shift_jis, gbk, euc-kr, cp866 or cp1252)",
+                name
+            ),
+        }
+    }
+}
+
+impl NameEncoding {
+    pub fn decode(&self, binary: &[u8]) -> String {
+        /*
+        Decodes the raw bytes of a name or comment, bytes which are not valid in the
+        code page are replaced with U+FFFD.
+
+        # Arguments
+        * `binary: &[u8]` The raw bytes as stored in the archive
+        */
+        match self.encoding() {
+            Some(encoding) => encoding.decode_without_bom_handling(binary).0.into_owned(),
+            None => binary
+                .iter()
+                .map(|byte| match byte {
+                    0x00..=0x7F => *byte as char,
+                    _ => CP437_HIGH[(*byte - 0x80) as usize],
+                })
+                .collect(),
+        }
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        /*
+        CP437 is not part of the WHATWG encodings, it is decoded with our own table
+        */
+        match self {
+            NameEncoding::Cp437 => None,
+            NameEncoding::ShiftJis => Some(SHIFT_JIS),
+            NameEncoding::Gbk => Some(GBK),
+            NameEncoding::EucKr => Some(EUC_KR),
+            NameEncoding::Cp866 => Some(IBM866),
+            NameEncoding::Cp1252 => Some(WINDOWS_1252),
+        }
+    }
+}
+
+impl std::str::FromStr for NameEncoding {
+    type Err = EncodingError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "cp437" | "ibm437" | "437" => Ok(NameEncoding::Cp437),
+            "shift-jis" | "sjis" | "cp932" => Ok(NameEncoding::ShiftJis),
+            "gbk" | "cp936" => Ok(NameEncoding::Gbk),
+            "euc-kr" | "cp949" => Ok(NameEncoding::EucKr),
+            "cp866" | "ibm866" | "866" => Ok(NameEncoding::Cp866),
+            "cp1252" | "windows-1252" | "1252" => Ok(NameEncoding::Cp1252),
+            _ => Err(EncodingError::UnknownEncoding(name.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for NameEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NameEncoding::Cp437 => write!(f, "cp437"),
+            NameEncoding::ShiftJis => write!(f, "shift_jis"),
+            NameEncoding::Gbk => write!(f, "gbk"),
+            NameEncoding::EucKr => write!(f, "euc-kr"),
+            NameEncoding::Cp866 => write!(f, "cp866"),
+            NameEncoding::Cp1252 => write!(f, "cp1252"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cd::{Cd, TextSource};
+    use bytes::Bytes;
+    use std::str::FromStr;
+
+    fn cd_with_unicode_path(name: &[u8], unicode_crc: u32, unicode_name: &str) -> Cd {
+        let mut extra = vec![0x75, 0x70];
+        extra.extend_from_slice(&(5 + unicode_name.len() as u16).to_le_bytes());
+        extra.push(1);
+        extra.extend_from_slice(&unicode_crc.to_le_bytes());
+        extra.extend_from_slice(unicode_name.as_bytes());
+
+        let mut binary = vec![0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        binary.extend_from_slice(&[0; 12]);
+        binary.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        binary.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        binary.extend_from_slice(&[0; 14]);
+        binary.extend_from_slice(name);
+        binary.extend_from_slice(&extra);
+        Cd::from(Bytes::from(binary))
+    }
+
+    #[test]
+    fn cp437_decodes_the_upper_half() {
+        assert_eq!(NameEncoding::Cp437.decode(&[0x80]), "Ç");
+        assert_eq!(NameEncoding::Cp437.decode(&[0xE1]), "ß");
+        assert_eq!(NameEncoding::Cp437.decode(&[0xFF]), "\u{a0}");
+        assert_eq!(NameEncoding::Cp437.decode(b"Stra\xE1e.txt"), "Straße.txt");
+    }
+
+    #[test]
+    fn cp437_decodes_box_drawing_characters() {
+        assert_eq!(NameEncoding::Cp437.decode(&[0xC9, 0xCD, 0xBB]), "╔═╗");
+        assert_eq!(NameEncoding::Cp437.decode(&[0xB3, 0xC4, 0xC5]), "│─┼");
+        assert_eq!(NameEncoding::Cp437.decode(&[0xB0, 0xB1, 0xB2, 0xDB]), "░▒▓█");
+    }
+
+    #[test]
+    fn cp437_keeps_ascii() {
+        assert_eq!(NameEncoding::Cp437.decode(b"dir/file-1.txt"), "dir/file-1.txt");
+    }
+
+    #[test]
+    fn other_code_pages_use_their_own_table() {
+        assert_eq!(NameEncoding::Cp1252.decode(&[0x80]), "€");
+        assert_eq!(NameEncoding::Cp866.decode(&[0x80]), "А");
+        assert_eq!(NameEncoding::ShiftJis.decode(&[0x83, 0x65, 0x83, 0x58, 0x83, 0x67]), "テスト");
+    }
+
+    #[test]
+    fn name_encoding_parses_aliases() {
+        assert_eq!(NameEncoding::from_str("IBM437").unwrap(), NameEncoding::Cp437);
+        assert_eq!(NameEncoding::from_str("shift_jis").unwrap(), NameEncoding::ShiftJis);
+        assert_eq!(NameEncoding::from_str("windows-1252").unwrap(), NameEncoding::Cp1252);
+        assert!(NameEncoding::from_str("latin9").is_err());
+    }
+
+    #[test]
+    fn unicode_path_with_matching_crc_replaces_the_header() {
+        let name = b"Stra\xE1e.txt";
+        let cd = cd_with_unicode_path(name, crc32fast::hash(name), "Straße.txt");
+        assert_eq!(cd.file_name().unwrap(), "Straße.txt");
+        assert_eq!(cd.file_name_source(), TextSource::UnicodeExtra);
+    }
+
+    #[test]
+    fn unicode_path_with_mismatched_crc_falls_back_to_the_header() {
+        let name = b"\x80a.txt";
+        let cd = cd_with_unicode_path(name, crc32fast::hash(b"renamed.txt"), "renamed.txt");
+        assert_eq!(cd.file_name().unwrap(), "Ça.txt");
+        assert_eq!(cd.file_name_source(), TextSource::StaleUnicodeExtra);
+    }
+}
@@ -1,9 +1,21 @@
 use bytes::{Buf, Bytes};
 
+use crate::encoding::NameEncoding;
+
 #[derive(Debug)]
 pub struct Eocd {
     binary: Bytes,
     pub word_size: WordSize,
+    /*
+    The archive comment, which only the 32-bit EOCD carries. A ZIP64 EOCD record
+    gets the comment of the 32-bit EOCD it was located with.
+    */
+    comment: Bytes,
+    /*
+    Bytes of data prepended to the archive (a self-extracting stub, for instance),
+    which every offset the archive records is off by.
+    */
+    pub base_offset: u64,
 }
 
 #[derive(Debug, PartialEq)]
@@ -17,12 +29,40 @@ struct FieldMetaData {
     offset: usize,
 }
 
+/*
+The ZIP64 end of central directory locator. It sits directly in front of
+the 32-bit EOCD and names the absolute offset of the ZIP64 EOCD record.
+*/
+#[derive(Debug)]
+pub struct Eocd64Locator {
+    binary: Bytes,
+}
+
 #[derive(Debug)]
 pub enum EocdError {
     NotValidBinary,
     UnknownWordSize,
     AttributeNotPresent,
     NotImplemented,
+    Truncated,
+    InvalidUTF8ByteVector,
+}
+
+impl std::error::Error for EocdError {}
+
+impl std::fmt::Display for EocdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EocdError::NotValidBinary => write!(f, "EOCD signature not found"),
+            EocdError::UnknownWordSize => write!(f, "Unknown EOCD word size"),
+            EocdError::AttributeNotPresent => {
+                write!(f, "Attribute not present for this EOCD word size")
+            }
+            EocdError::NotImplemented => write!(f, "Not implemented"),
+            EocdError::Truncated => write!(f, "EOCD record is truncated"),
+            EocdError::InvalidUTF8ByteVector => write!(f, "Invalid UTF-8 byte vector"),
+        }
+    }
 }
 
 impl FieldMetaData {
@@ -39,11 +79,32 @@ impl Eocd {
     const EOCD_32_CHECKSUM: &str = "06054b50";
     const EOCD_64_CHECKSUM: &str = "06064b50";
 
+    /*
+    Fixed sizes of the two EOCD records, excluding the comment (32-bit) and
+    the extensible data sector (64-bit).
+    */
+    pub const EOCD_32_SIZE: usize = 22;
+    pub const EOCD_64_SIZE: usize = 56;
+
     pub fn from(binary: Bytes) -> Self {
-        Self {
-            word_size: Self::verify(&binary).unwrap(),
-            binary,
+        Self::try_from(binary).unwrap()
+    }
+
+    pub fn has_zip64_sentinel(&self) -> bool {
+        /*
+        A ZIP64 archive saturates one or more of the 32-bit EOCD fields
+        (0xFFFF or 0xFFFFFFFF) and stores the real value in the ZIP64 EOCD record.
+        */
+        if self.word_size == WordSize::Bit64 {
+            return false;
         }
+
+        self.number_of_this_disk() == 0xFFFF
+            || self.disk_where_cd_starts() == 0xFFFF
+            || self.number_of_central_directory_records_on_this_disk() == 0xFFFF
+            || self.total_number_of_central_directory_records() == 0xFFFF
+            || self.size_of_central_directory() == 0xFFFFFFFF
+            || self.offset_of_start_of_central_directory() == 0xFFFFFFFF
     }
 
     pub fn number_of_this_disk(&self) -> u32 {
@@ -189,20 +250,29 @@ impl Eocd {
         Description: Comment length
         32-Bit offset: 20
         32-Bit size: 2
+        64-Bit: the comment length of the 32-bit EOCD, the ZIP64 record has no comment
         */
         let metadata_32 = FieldMetaData {
             size: 2,
             offset: 20,
         }
         .slice_range();
-        let metadata_64 = FieldMetaData { size: 8, offset: 4 }.slice_range();
 
         match self.word_size {
             WordSize::Bit32 => self.binary.slice(metadata_32).get_u16_le() as u64,
-            WordSize::Bit64 => self.binary.slice(metadata_64).get_u64_le() - 44,
+            WordSize::Bit64 => self.comment.len() as u64,
         }
     }
 
+    pub fn size_of_extensible_data_sector(&self) -> Result<u64, EocdError> {
+        /*
+        Description: zip64 extensible data sector
+        64-Bit offset: 56
+        64-Bit size: size of the EOCD64 minus 12, minus the 44 bytes of fixed fields
+        */
+        Ok(self.size_of_eocd64_minus_12()?.saturating_sub(44))
+    }
+
     pub fn size_of_eocd64_minus_12(&self) -> Result<u64, EocdError> {
         /*
         Description: Size of the EOCD64 minus 12
@@ -253,33 +323,44 @@ impl Eocd {
         }
     }
 
-    pub fn comment(&self) -> Result<String, EocdError> {
+    pub fn comment_raw(&self) -> Bytes {
         /*
         Description: Comment
         32-Bit offset: 22
         32-Bit size: n
-        64-Bit offset: 56
-        64-Bit size: n
+        64-Bit: the comment of the 32-bit EOCD
+
+        A comment running past the data pulled from the end of the archive is cut
+        short, as the archive itself ends there.
         */
-        // We need the comment length to calculate "n"
-        let comment_length = self.zip_file_comment_length();
+        self.comment.clone()
+    }
 
-        let metadata_32 = FieldMetaData {
-            size: comment_length as usize,
-            offset: 22,
-        }
-        .slice_range();
-        let metadata_64 = FieldMetaData {
-            size: comment_length as usize,
-            offset: 56,
-        }
-        .slice_range();
+    pub fn comment(&self) -> Result<String, EocdError> {
+        String::from_utf8(self.comment.to_vec()).map_err(|_| EocdError::InvalidUTF8ByteVector)
+    }
 
-        let bytes_vec = match self.word_size {
-            WordSize::Bit32 => self.binary.slice(metadata_32).to_vec(),
-            WordSize::Bit64 => self.binary.slice(metadata_64).to_vec(),
-        };
-        Ok(String::from_utf8(bytes_vec).expect("Invalid Comment"))
+    pub fn comment_lossy(&self) -> String {
+        /*
+        The comment as UTF-8, with the invalid bytes replaced
+        */
+        String::from_utf8_lossy(&self.comment).into_owned()
+    }
+
+    pub fn comment_decoded(&self, encoding: NameEncoding) -> String {
+        /*
+        The comment decoded with a code page. The EOCD has no flag to mark its comment
+        as UTF-8, writers use the same code page as for the entry names.
+        */
+        encoding.decode(&self.comment)
+    }
+
+    pub fn with_comment(mut self, comment: Bytes) -> Self {
+        /*
+        Hands the comment of the 32-bit EOCD to the ZIP64 EOCD record it points to
+        */
+        self.comment = comment;
+        self
     }
 
     fn verify(binary: &Bytes) -> Result<WordSize, EocdError> {
@@ -290,10 +371,20 @@ impl Eocd {
         */
         let metadata = FieldMetaData { size: 4, offset: 0 }.slice_range();
 
+        if binary.len() < metadata.end {
+            return Err(EocdError::Truncated);
+        }
+
         let first_four_bytes = binary.slice(metadata);
         if first_four_bytes == Eocd::eocd_32_checksum() {
+            if binary.len() < Self::EOCD_32_SIZE {
+                return Err(EocdError::Truncated);
+            }
             return Ok(WordSize::Bit32);
         } else if first_four_bytes == Eocd::eocd_64_checksum() {
+            if binary.len() < Self::EOCD_64_SIZE {
+                return Err(EocdError::Truncated);
+            }
             return Ok(WordSize::Bit64);
         }
 
@@ -329,3 +420,81 @@ impl Eocd {
         Bytes::from(little_endian_bytes)
     }
 }
+
+impl TryFrom<Bytes> for Eocd {
+    type Error = EocdError;
+
+    fn try_from(binary: Bytes) -> Result<Self, Self::Error> {
+        let word_size = Self::verify(&binary)?;
+        let mut eocd = Self {
+            word_size,
+            binary,
+            comment: Bytes::new(),
+            base_offset: 0,
+        };
+
+        if eocd.word_size == WordSize::Bit32 {
+            let comment_end = (Self::EOCD_32_SIZE + eocd.zip_file_comment_length() as usize).min(eocd.binary.len());
+            eocd.comment = eocd.binary.slice(Self::EOCD_32_SIZE..comment_end);
+        }
+        Ok(eocd)
+    }
+}
+
+impl Eocd64Locator {
+    /*
+    This checksum signifies the start of the ZIP64 EOCD locator
+    */
+    const LOCATOR_CHECKSUM: &'static [u8] = &[0x50, 0x4b, 0x06, 0x07];
+
+    pub const SIZE: usize = 20;
+
+    pub fn disk_with_zip64_eocd(&self) -> u32 {
+        /*
+        Description: Number of the disk with the start of the ZIP64 EOCD
+        Offset: 4
+        Size: 4
+        */
+        let metadata = FieldMetaData { size: 4, offset: 4 }.slice_range();
+        self.binary.slice(metadata).get_u32_le()
+    }
+
+    pub fn offset_of_zip64_eocd(&self) -> u64 {
+        /*
+        Description: Relative offset of the ZIP64 EOCD record
+        Offset: 8
+        Size: 8
+        */
+        let metadata = FieldMetaData { size: 8, offset: 8 }.slice_range();
+        self.binary.slice(metadata).get_u64_le()
+    }
+
+    pub fn total_number_of_disks(&self) -> u32 {
+        /*
+        Description: Total number of disks
+        Offset: 16
+        Size: 4
+        */
+        let metadata = FieldMetaData {
+            size: 4,
+            offset: 16,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u32_le()
+    }
+}
+
+impl TryFrom<Bytes> for Eocd64Locator {
+    type Error = EocdError;
+
+    fn try_from(binary: Bytes) -> Result<Self, Self::Error> {
+        if binary.len() < Self::SIZE {
+            return Err(EocdError::Truncated);
+        }
+        if &binary[0..4] != Self::LOCATOR_CHECKSUM {
+            return Err(EocdError::NotValidBinary);
+        }
+
+        Ok(Self { binary })
+    }
+}
@@ -0,0 +1,666 @@
+use bytes::{Buf, Bytes};
+
+/*
+The extra field of a Central Directory entry (and of a local file header) is a
+sequence of records, each one made up of:
+
+Description: Header ID
+Offset: 0
+Size: 2
+
+Description: Data size (s)
+Offset: 2
+Size: 2
+
+Description: Data
+Offset: 4
+Size: s
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExtraField {
+    Zip64ExtendedInformation(Zip64ExtendedInformation),
+    Ntfs(Ntfs),
+    ExtendedTimestamp(ExtendedTimestamp),
+    InfoZipUnix(InfoZipUnix),
+    InfoZipUnixLegacy(InfoZipUnixLegacy),
+    UnicodePath(UnicodeExtra),
+    UnicodeComment(UnicodeExtra),
+    WinZipAes(WinZipAes),
+    Zipalign(Zipalign),
+    Unknown { header_id: u16, data: Bytes },
+}
+
+/*
+ZIP64 extended information (0x0001).
+
+The record only holds the fields whose 32-bit counterparts in the header are
+saturated, so it can only be interpreted together with that header.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zip64ExtendedInformation {
+    pub data: Bytes,
+}
+
+/*
+The values resolved from a ZIP64 extended information record. A value is
+`None` when its 32-bit counterpart is not saturated or the record is too short.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Zip64Values {
+    pub uncompressed_size: Option<u64>,
+    pub compressed_size: Option<u64>,
+    pub local_header_offset: Option<u64>,
+    pub disk_start_number: Option<u32>,
+}
+
+/*
+NTFS times (0x000a), as 100ns intervals since 1601-01-01 UTC.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ntfs {
+    pub modification_time: u64,
+    pub access_time: u64,
+    pub creation_time: u64,
+}
+
+/*
+Extended timestamp (0x5455), as seconds since the Unix epoch.
+
+The flags announce which times exist in the local header, the Central
+Directory copy usually only carries the modification time.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedTimestamp {
+    pub flags: u8,
+    pub modification_time: Option<i32>,
+    pub access_time: Option<i32>,
+    pub creation_time: Option<i32>,
+}
+
+/*
+Info-ZIP Unix UID/GID (0x7875).
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfoZipUnix {
+    pub version: u8,
+    pub uid: u64,
+    pub gid: u64,
+}
+
+/*
+Info-ZIP Unix, the original type (0x5855), as seconds since the Unix epoch.
+
+Only the local header copy carries the UID and GID.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct InfoZipUnixLegacy {
+    pub access_time: i32,
+    pub modification_time: i32,
+    pub uid: Option<u16>,
+    pub gid: Option<u16>,
+}
+
+/*
+Info-ZIP Unicode Path (0x7075) and Unicode Comment (0x6375).
+
+The CRC-32 is that of the name or comment stored in the header, so a stale
+Unicode field can be detected.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnicodeExtra {
+    pub version: u8,
+    pub crc_32: u32,
+    pub data: Bytes,
+}
+
+/*
+WinZip AES encryption (0x9901).
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WinZipAes {
+    pub vendor_version: u16,
+    pub vendor_id: [u8; 2],
+    pub strength: u8,
+    pub compression_method: u16,
+}
+
+/*
+Android zipalign padding (0xd935).
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zipalign {
+    pub alignment: u16,
+    pub padding: usize,
+}
+
+/*
+Iterator over the records of an extra field.
+
+Records with a known header ID but an unexpected layout come back as
+`ExtraField::Unknown`. A record whose size runs past the end of the block is
+returned as `ExtraField::Unknown` holding the remaining bytes, and ends the
+iteration.
+*/
+#[derive(Debug, Clone)]
+pub struct ExtraFields {
+    binary: Bytes,
+}
+
+impl ExtraField {
+    pub const ZIP64: u16 = 0x0001;
+    pub const NTFS: u16 = 0x000a;
+    pub const EXTENDED_TIMESTAMP: u16 = 0x5455;
+    pub const INFO_ZIP_UNIX: u16 = 0x7875;
+    pub const INFO_ZIP_UNIX_LEGACY: u16 = 0x5855;
+    pub const UNICODE_PATH: u16 = 0x7075;
+    pub const UNICODE_COMMENT: u16 = 0x6375;
+    pub const WINZIP_AES: u16 = 0x9901;
+    pub const ZIPALIGN: u16 = 0xd935;
+
+    pub fn parse(header_id: u16, data: Bytes) -> Self {
+        let parsed = match header_id {
+            Self::ZIP64 => Some(ExtraField::Zip64ExtendedInformation(
+                Zip64ExtendedInformation { data: data.clone() },
+            )),
+            Self::NTFS => Ntfs::parse(&data).map(ExtraField::Ntfs),
+            Self::EXTENDED_TIMESTAMP => {
+                ExtendedTimestamp::parse(&data).map(ExtraField::ExtendedTimestamp)
+            }
+            Self::INFO_ZIP_UNIX => InfoZipUnix::parse(&data).map(ExtraField::InfoZipUnix),
+            Self::INFO_ZIP_UNIX_LEGACY => InfoZipUnixLegacy::parse(&data).map(ExtraField::InfoZipUnixLegacy),
+            Self::UNICODE_PATH => UnicodeExtra::parse(&data).map(ExtraField::UnicodePath),
+            Self::UNICODE_COMMENT => UnicodeExtra::parse(&data).map(ExtraField::UnicodeComment),
+            Self::WINZIP_AES => WinZipAes::parse(&data).map(ExtraField::WinZipAes),
+            Self::ZIPALIGN => Zipalign::parse(&data).map(ExtraField::Zipalign),
+            _ => None,
+        };
+
+        parsed.unwrap_or(ExtraField::Unknown { header_id, data })
+    }
+
+    pub fn header_id(&self) -> u16 {
+        match self {
+            ExtraField::Zip64ExtendedInformation(_) => Self::ZIP64,
+            ExtraField::Ntfs(_) => Self::NTFS,
+            ExtraField::ExtendedTimestamp(_) => Self::EXTENDED_TIMESTAMP,
+            ExtraField::InfoZipUnix(_) => Self::INFO_ZIP_UNIX,
+            ExtraField::InfoZipUnixLegacy(_) => Self::INFO_ZIP_UNIX_LEGACY,
+            ExtraField::UnicodePath(_) => Self::UNICODE_PATH,
+            ExtraField::UnicodeComment(_) => Self::UNICODE_COMMENT,
+            ExtraField::WinZipAes(_) => Self::WINZIP_AES,
+            ExtraField::Zipalign(_) => Self::ZIPALIGN,
+            ExtraField::Unknown { header_id, .. } => *header_id,
+        }
+    }
+}
+
+impl Zip64ExtendedInformation {
+    pub fn resolve(
+        &self,
+        uncompressed_size: bool,
+        compressed_size: bool,
+        local_header_offset: bool,
+        disk_start_number: bool,
+    ) -> Zip64Values {
+        /*
+        Per APPNOTE 4.5.3 the record only holds the fields whose counterparts in the
+        header are saturated (0xFFFFFFFF or 0xFFFF), always in this order:
+
+        Description: Original uncompressed file size
+        Size: 8
+
+        Description: Size of compressed data
+        Size: 8
+
+        Description: Offset of local header record
+        Size: 8
+
+        Description: Number of the disk on which this file starts
+        Size: 4
+
+        # Arguments
+        * `uncompressed_size: bool` whether the header saturates the uncompressed size
+        * `compressed_size: bool` whether the header saturates the compressed size
+        * `local_header_offset: bool` whether the header saturates the local header offset
+        * `disk_start_number: bool` whether the header saturates the disk number
+        */
+        let mut data = self.data.clone();
+        let mut next_u64 = |present: bool| {
+            if present && data.len() >= 8 {
+                Some(data.get_u64_le())
+            } else {
+                None
+            }
+        };
+
+        let uncompressed_size = next_u64(uncompressed_size);
+        let compressed_size = next_u64(compressed_size);
+        let local_header_offset = next_u64(local_header_offset);
+        let disk_start_number = if disk_start_number && data.len() >= 4 {
+            Some(data.get_u32_le())
+        } else {
+            None
+        };
+
+        Zip64Values {
+            uncompressed_size,
+            compressed_size,
+            local_header_offset,
+            disk_start_number,
+        }
+    }
+
+    pub fn resolve_local(&self, uncompressed_size: bool, compressed_size: bool) -> Zip64Values {
+        /*
+        Per APPNOTE 4.5.3 the record of a local file header must hold both the
+        uncompressed and the compressed size, whichever of them is saturated in the
+        header, in this order:
+
+        Description: Original uncompressed file size
+        Size: 8
+
+        Description: Size of compressed data
+        Size: 8
+
+        A value is only taken over when its header field is saturated. A record too
+        short to hold both sizes was written the Central Directory way, and is read
+        like one.
+
+        # Arguments
+        * `uncompressed_size: bool` whether the header saturates the uncompressed size
+        * `compressed_size: bool` whether the header saturates the compressed size
+        */
+        if self.data.len() < 16 {
+            return self.resolve(uncompressed_size, compressed_size, false, false);
+        }
+
+        let mut data = self.data.clone();
+        let uncompressed = data.get_u64_le();
+        let compressed = data.get_u64_le();
+        Zip64Values {
+            uncompressed_size: uncompressed_size.then_some(uncompressed),
+            compressed_size: compressed_size.then_some(compressed),
+            ..Zip64Values::default()
+        }
+    }
+}
+
+impl ExtraFields {
+    pub fn new(binary: Bytes) -> Self {
+        Self { binary }
+    }
+}
+
+impl Iterator for ExtraFields {
+    type Item = ExtraField;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        /*
+        Fewer than 4 bytes can not hold a record header, writers such as zipalign
+        are known to leave a few bytes of padding at the end of the block.
+        */
+        if self.binary.len() < 4 {
+            return None;
+        }
+
+        let mut header = self.binary.slice(0..4);
+        let header_id = header.get_u16_le();
+        let size = header.get_u16_le() as usize;
+        self.binary.advance(4);
+
+        if size > self.binary.len() {
+            let data = self.binary.split_off(0);
+            return Some(ExtraField::Unknown { header_id, data });
+        }
+
+        let data = self.binary.split_to(size);
+        Some(ExtraField::parse(header_id, data))
+    }
+}
+
+impl Ntfs {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        4 reserved bytes followed by attribute records of
+        tag (2), size (2), data (size). Tag 0x0001 holds the three file times.
+        */
+        let mut data = data.clone();
+        if data.len() < 4 {
+            return None;
+        }
+        data.advance(4);
+
+        while data.len() >= 4 {
+            let tag = data.get_u16_le();
+            let size = data.get_u16_le() as usize;
+            if size > data.len() {
+                return None;
+            }
+            if tag == 0x0001 && size == 24 {
+                return Some(Self {
+                    modification_time: data.get_u64_le(),
+                    access_time: data.get_u64_le(),
+                    creation_time: data.get_u64_le(),
+                });
+            }
+            data.advance(size);
+        }
+
+        None
+    }
+}
+
+impl ExtendedTimestamp {
+    fn parse(data: &Bytes) -> Option<Self> {
+        let mut data = data.clone();
+        if data.is_empty() {
+            return None;
+        }
+
+        let flags = data.get_u8();
+        let mut next_time = |flag: u8| {
+            if flags & flag != 0 && data.len() >= 4 {
+                Some(data.get_i32_le())
+            } else {
+                None
+            }
+        };
+        let modification_time = next_time(0x01);
+        let access_time = next_time(0x02);
+        let creation_time = next_time(0x04);
+
+        Some(Self {
+            flags,
+            modification_time,
+            access_time,
+            creation_time,
+        })
+    }
+}
+
+impl InfoZipUnix {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        version (1), UID size (1), UID (n), GID size (1), GID (m)
+        */
+        let mut data = data.clone();
+        if data.len() < 2 {
+            return None;
+        }
+
+        let version = data.get_u8();
+        let uid = Self::next_id(&mut data)?;
+        let gid = Self::next_id(&mut data)?;
+
+        Some(Self { version, uid, gid })
+    }
+
+    fn next_id(data: &mut Bytes) -> Option<u64> {
+        if data.is_empty() {
+            return None;
+        }
+        let size = data.get_u8() as usize;
+        if size > 8 || size > data.len() {
+            return None;
+        }
+        Some(data.get_uint_le(size))
+    }
+}
+
+impl InfoZipUnixLegacy {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        access time (4), modification time (4), then UID (2) and GID (2) in the local header
+        */
+        let mut data = data.clone();
+        if data.len() < 8 {
+            return None;
+        }
+
+        let access_time = data.get_i32_le();
+        let modification_time = data.get_i32_le();
+        let (uid, gid) = match data.len() >= 4 {
+            true => (Some(data.get_u16_le()), Some(data.get_u16_le())),
+            false => (None, None),
+        };
+
+        Some(Self {
+            access_time,
+            modification_time,
+            uid,
+            gid,
+        })
+    }
+}
+
+impl UnicodeExtra {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        version (1), CRC-32 of the header field (4), UTF-8 data (n)
+        */
+        let mut data = data.clone();
+        if data.len() < 5 {
+            return None;
+        }
+
+        Some(Self {
+            version: data.get_u8(),
+            crc_32: data.get_u32_le(),
+            data,
+        })
+    }
+}
+
+impl WinZipAes {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        vendor version (2), vendor ID (2), strength (1), compression method (2)
+        */
+        let mut data = data.clone();
+        if data.len() != 7 {
+            return None;
+        }
+
+        Some(Self {
+            vendor_version: data.get_u16_le(),
+            vendor_id: [data.get_u8(), data.get_u8()],
+            strength: data.get_u8(),
+            compression_method: data.get_u16_le(),
+        })
+    }
+}
+
+impl Zipalign {
+    fn parse(data: &Bytes) -> Option<Self> {
+        /*
+        alignment (2), zero padding (n)
+        */
+        let mut data = data.clone();
+        if data.len() < 2 {
+            return None;
+        }
+
+        Some(Self {
+            alignment: data.get_u16_le(),
+            padding: data.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(header_id: u16, data: &[u8]) -> Vec<u8> {
+        let mut record = header_id.to_le_bytes().to_vec();
+        record.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        record.extend_from_slice(data);
+        record
+    }
+
+    fn fields(binary: Vec<u8>) -> Vec<ExtraField> {
+        ExtraFields::new(Bytes::from(binary)).collect()
+    }
+
+    #[test]
+    fn reads_consecutive_records() {
+        let mut binary = record(0xcafe, b"ab");
+        binary.extend(record(ExtraField::ZIPALIGN, &[4, 0, 0, 0]));
+        let fields = fields(binary);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].header_id(), 0xcafe);
+        assert_eq!(fields[1], ExtraField::Zipalign(Zipalign { alignment: 4, padding: 2 }));
+    }
+
+    #[test]
+    fn keeps_unknown_header_id_as_raw() {
+        assert_eq!(
+            fields(record(0xcafe, b"raw")),
+            vec![ExtraField::Unknown { header_id: 0xcafe, data: Bytes::from_static(b"raw") }]
+        );
+    }
+
+    #[test]
+    fn skips_trailing_padding() {
+        let mut binary = record(0xcafe, b"");
+        binary.extend_from_slice(&[0, 0, 0]);
+        assert_eq!(fields(binary).len(), 1);
+    }
+
+    #[test]
+    fn oversized_length_ends_iteration() {
+        let mut binary = 0xcafeu16.to_le_bytes().to_vec();
+        binary.extend_from_slice(&100u16.to_le_bytes());
+        binary.extend_from_slice(b"short");
+        binary.extend(record(0xbeef, b""));
+        let fields = fields(binary);
+        assert_eq!(fields.len(), 1);
+        assert_eq!(
+            fields[0],
+            ExtraField::Unknown {
+                header_id: 0xcafe,
+                data: Bytes::from_static(b"short\xef\xbe\x00\x00")
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_known_record_is_kept_as_raw() {
+        let data = [1, 0, 0, 0, 1, 0, 24, 0, 1, 2, 3];
+        assert_eq!(
+            fields(record(ExtraField::NTFS, &data)),
+            vec![ExtraField::Unknown { header_id: ExtraField::NTFS, data: Bytes::copy_from_slice(&data) }]
+        );
+    }
+
+    #[test]
+    fn parses_ntfs() {
+        let mut data = vec![0, 0, 0, 0, 1, 0, 24, 0];
+        for time in [132_000_000_000_000_000u64, 132_000_000_000_000_001, 116_444_736_000_000_000] {
+            data.extend_from_slice(&time.to_le_bytes());
+        }
+        assert_eq!(
+            fields(record(ExtraField::NTFS, &data)),
+            vec![ExtraField::Ntfs(Ntfs {
+                modification_time: 132_000_000_000_000_000,
+                access_time: 132_000_000_000_000_001,
+                creation_time: 116_444_736_000_000_000,
+            })]
+        );
+    }
+
+    #[test]
+    fn parses_extended_timestamp() {
+        let mut data = vec![0x07];
+        data.extend_from_slice(&1_600_000_000i32.to_le_bytes());
+        data.extend_from_slice(&(-1i32).to_le_bytes());
+        assert_eq!(
+            fields(record(ExtraField::EXTENDED_TIMESTAMP, &data)),
+            vec![ExtraField::ExtendedTimestamp(ExtendedTimestamp {
+                flags: 0x07,
+                modification_time: Some(1_600_000_000),
+                access_time: Some(-1),
+                creation_time: None,
+            })]
+        );
+    }
+
+    #[test]
+    fn parses_info_zip_unix() {
+        let data = [1, 4, 0xe8, 0x03, 0, 0, 2, 0x64, 0x00];
+        assert_eq!(
+            fields(record(ExtraField::INFO_ZIP_UNIX, &data)),
+            vec![ExtraField::InfoZipUnix(InfoZipUnix { version: 1, uid: 1000, gid: 100 })]
+        );
+    }
+
+    #[test]
+    fn parses_unicode_path() {
+        let mut data = vec![1];
+        data.extend_from_slice(&0xdeadbeefu32.to_le_bytes());
+        data.extend_from_slice("naïve.txt".as_bytes());
+        assert_eq!(
+            fields(record(ExtraField::UNICODE_PATH, &data)),
+            vec![ExtraField::UnicodePath(UnicodeExtra {
+                version: 1,
+                crc_32: 0xdeadbeef,
+                data: Bytes::from("naïve.txt"),
+            })]
+        );
+    }
+
+    fn zip64(values: &[u64]) -> Zip64ExtendedInformation {
+        let data: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+        Zip64ExtendedInformation { data: Bytes::from(data) }
+    }
+
+    #[test]
+    fn resolve_reads_every_saturated_field() {
+        let mut zip64 = zip64(&[1, 2, 3]);
+        let mut data = zip64.data.to_vec();
+        data.extend_from_slice(&4u32.to_le_bytes());
+        zip64.data = Bytes::from(data);
+        assert_eq!(
+            zip64.resolve(true, true, true, true),
+            Zip64Values {
+                uncompressed_size: Some(1),
+                compressed_size: Some(2),
+                local_header_offset: Some(3),
+                disk_start_number: Some(4),
+            }
+        );
+    }
+
+    #[test]
+    fn resolve_compressed_size_only() {
+        assert_eq!(
+            zip64(&[7]).resolve(false, true, false, false),
+            Zip64Values { compressed_size: Some(7), ..Zip64Values::default() }
+        );
+    }
+
+    #[test]
+    fn resolve_local_header_offset_only() {
+        assert_eq!(
+            zip64(&[0x1_0000_0000]).resolve(false, false, true, false),
+            Zip64Values { local_header_offset: Some(0x1_0000_0000), ..Zip64Values::default() }
+        );
+    }
+
+    #[test]
+    fn resolve_record_shorter_than_saturated_fields() {
+        assert_eq!(
+            zip64(&[1]).resolve(true, true, true, false),
+            Zip64Values { uncompressed_size: Some(1), ..Zip64Values::default() }
+        );
+    }
+
+    #[test]
+    fn resolve_local_reads_both_sizes() {
+        assert_eq!(
+            zip64(&[1, 2]).resolve_local(false, true),
+            Zip64Values { compressed_size: Some(2), ..Zip64Values::default() }
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use bytes::Bytes;
+use flate2::read::DeflateDecoder;
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::cd::Cd;
+use crate::compression::CompressionMethod;
+use crate::descriptor::DataDescriptor;
+use crate::flags::GeneralPurposeFlags;
+use crate::lfh::Lfh;
+use crate::ZipSniperError;
+
+/*
+Upper bound of the buffer reserved up front for an entry, the uncompressed size
+comes from the archive and can not be trusted.
+*/
+const MAX_PREALLOCATION: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum ExtractError {
+    EntryNotFound,
+    Encrypted,
+    UnsupportedCompressionMethod(CompressionMethod),
+    Decompression(std::io::Error),
+    SizeExceeded(u64),
+    CrcMismatch { source: RecordSource, expected: u32, actual: u32 },
+    Io(std::io::Error),
+}
+
+/*
+The structure a value of an entry was recorded in.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordSource {
+    CentralDirectory,
+    LocalHeader,
+    DataDescriptor,
+}
+
+impl std::fmt::Display for RecordSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RecordSource::CentralDirectory => write!(f, "CD record"),
+            RecordSource::LocalHeader => write!(f, "local file header"),
+            RecordSource::DataDescriptor => write!(f, "data descriptor"),
+        }
+    }
+}
+
+/*
+A value recorded for every entry by more than one structure.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordField {
+    Crc32,
+    CompressedSize,
+    UncompressedSize,
+    CompressionMethod,
+}
+
+impl std::fmt::Display for RecordField {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RecordField::Crc32 => write!(f, "CRC-32"),
+            RecordField::CompressedSize => write!(f, "compressed size"),
+            RecordField::UncompressedSize => write!(f, "uncompressed size"),
+            RecordField::CompressionMethod => write!(f, "compression method"),
+        }
+    }
+}
+
+/*
+A value the local file header or data descriptor of an entry records
+differently than its CD record.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discrepancy {
+    pub field: RecordField,
+    pub source: RecordSource,
+    pub recorded: u64,
+    pub central_directory: u64,
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.field {
+            RecordField::Crc32 => write!(
+                f,
+                "{}: the {} records {:08x} but the CD record has {:08x}",
+                self.field, self.source, self.recorded, self.central_directory
+            ),
+            _ => write!(
+                f,
+                "{}: the {} records {} but the CD record has {}",
+                self.field, self.source, self.recorded, self.central_directory
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl std::fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExtractError::EntryNotFound => write!(f, "Entry not found"),
+            ExtractError::Encrypted => write!(f, "Entry is encrypted"),
+            ExtractError::UnsupportedCompressionMethod(method) => {
+                write!(f, "Unsupported compression method {} ({})", method.name(), method.id())
+            }
+            ExtractError::Decompression(err) => write!(f, "Decompression failed: {}", err),
+            ExtractError::SizeExceeded(expected) => write!(
+                f,
+                "Decompressed content exceeds the uncompressed size of {} bytes the CD record has",
+                expected
+            ),
+            ExtractError::CrcMismatch { source, expected, actual } => write!(
+                f,
+                "CRC-32 mismatch: the {} records {:08x} but the content has {:08x}",
+                source, expected, actual
+            ),
+            ExtractError::Io(err) => write!(f, "IO Error: {}", err),
+        }
+    }
+}
+
+/*
+An entry name that can not be turned into a path below the output directory.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnsafePath {
+    ParentDirectory,
+    InvalidCharacter(char),
+    Empty,
+}
+
+impl std::fmt::Display for UnsafePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnsafePath::ParentDirectory => write!(f, "path escapes the output directory (..)"),
+            UnsafePath::InvalidCharacter(character) => {
+                write!(f, "path contains the character {:?}", character)
+            }
+            UnsafePath::Empty => write!(f, "path is empty"),
+        }
+    }
+}
+
+/*
+Why an entry was not written by a bulk extraction.
+*/
+#[derive(Debug)]
+pub enum SkipReason {
+    NotIncluded,
+    Excluded,
+    UnsafePath(UnsafePath),
+    Failed(ZipSniperError),
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SkipReason::NotIncluded => write!(f, "not matched by an include pattern"),
+            SkipReason::Excluded => write!(f, "matched by an exclude pattern"),
+            SkipReason::UnsafePath(reason) => write!(f, "unsafe path: {}", reason),
+            SkipReason::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExtractedEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /*
+    The entry name had to be rewritten to give a safe path.
+    */
+    pub rewritten: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ExtractSummary {
+    pub extracted: Vec<ExtractedEntry>,
+    pub skipped: Vec<(String, SkipReason)>,
+}
+
+/*
+The outcome of verifying every selected entry, holding the CRC-32 of the
+entries that passed.
+*/
+#[derive(Debug, Default)]
+pub struct VerifySummary {
+    pub entries: Vec<(String, Result<u32, ZipSniperError>)>,
+    pub skipped: Vec<(String, SkipReason)>,
+    /*
+    The entries whose local structures disagree with their CD record.
+    */
+    pub discrepancies: Vec<(String, Vec<Discrepancy>)>,
+}
+
+impl VerifySummary {
+    pub fn passed(&self) -> usize {
+        self.entries.iter().filter(|(_, result)| result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.entries.len() - self.passed()
+    }
+}
+
+pub fn sanitise_path(name: &str) -> Result<(PathBuf, bool), UnsafePath> {
+    /*
+    Turns an entry name into a relative path that stays below the output directory.
+    Returns the path and whether the name had to be rewritten to get there.
+
+    Backslash separators are turned into forward slashes, drive letters and leading
+    separators (absolute and UNC paths) are stripped, and empty or `.` components
+    are dropped. Names with a `..` component, a NUL byte or a `:` past the drive
+    letter (NTFS alternate data streams) are rejected.
+
+    # Arguments
+    * `name: &str` The entry name as stored in the archive
+    */
+    let mut relative = name.replace('\\', "/");
+    let bytes = relative.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        relative.drain(..2);
+    }
+
+    let mut path = PathBuf::new();
+    let mut components = Vec::new();
+    for component in relative.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return Err(UnsafePath::ParentDirectory),
+            _ => (),
+        }
+        if let Some(character) = component.chars().find(|c| *c == '\0' || *c == ':') {
+            return Err(UnsafePath::InvalidCharacter(character));
+        }
+        components.push(component);
+        path.push(component);
+    }
+
+    if components.is_empty() {
+        return Err(UnsafePath::Empty);
+    }
+
+    let mut normalised = components.join("/");
+    if name.ends_with('/') || name.ends_with('\\') {
+        normalised.push('/');
+    }
+    Ok((path, normalised != name))
+}
+
+pub fn decompress(
+    compression_method: CompressionMethod,
+    data: Bytes,
+    uncompressed_size: u64,
+) -> Result<Bytes, ExtractError> {
+    /*
+    Turns the compressed data of an entry back into its original content.
+
+    The output is cut off one byte past <uncompressed_size>, so a stream which
+    expands further than the CD record claims (a zip bomb) is rejected before it
+    can exhaust memory.
+
+    # Arguments
+    * `compression_method: CompressionMethod` The compression method of the entry
+    * `data: Bytes` The compressed data
+    * `uncompressed_size: u64` The size of the original content the CD record has
+    */
+    match compression_method {
+        CompressionMethod::Stored => Ok(data),
+        CompressionMethod::Deflated => {
+            let mut output = Vec::with_capacity(uncompressed_size.min(MAX_PREALLOCATION) as usize);
+            DeflateDecoder::new(data.as_ref())
+                .take(uncompressed_size.saturating_add(1))
+                .read_to_end(&mut output)
+                .map_err(ExtractError::Decompression)?;
+            if output.len() as u64 > uncompressed_size {
+                return Err(ExtractError::SizeExceeded(uncompressed_size));
+            }
+            Ok(Bytes::from(output))
+        }
+        method => Err(ExtractError::UnsupportedCompressionMethod(method)),
+    }
+}
+
+pub fn cross_check(cd: &Cd, lfh: &Lfh, descriptor: Option<&DataDescriptor>) -> Vec<Discrepancy> {
+    /*
+    Compares the values the local file header and data descriptor of an entry record
+    against its CD record.
+
+    With general purpose bit 3 set, the local file header leaves the CRC-32 and sizes
+    zero and the data descriptor carries them instead, so those local header fields
+    are only compared when they are filled in.
+
+    # Arguments
+    * `cd: &Cd` The CD record of the entry
+    * `lfh: &Lfh` The local file header of the entry
+    * `descriptor: Option<&DataDescriptor>` The data descriptor of the entry, if it has one
+    */
+    let deferred = lfh.flags().contains(GeneralPurposeFlags::DATA_DESCRIPTOR);
+    let mut recorded = vec![(
+        RecordField::CompressionMethod,
+        RecordSource::LocalHeader,
+        lfh.compression_method() as u64,
+        cd.compression_method() as u64,
+    )];
+
+    let local = [
+        (RecordField::Crc32, lfh.crc_32_of_uncompressed_data() as u64, cd.crc_32_of_uncompressed_data() as u64),
+        (RecordField::CompressedSize, lfh.compressed_size(), cd.compressed_size()),
+        (RecordField::UncompressedSize, lfh.uncompressed_size(), cd.uncompressed_size()),
+    ];
+    for (field, value, expected) in local {
+        if !deferred || value != 0 {
+            recorded.push((field, RecordSource::LocalHeader, value, expected));
+        }
+    }
+
+    if let Some(descriptor) = descriptor {
+        recorded.extend([
+            (
+                RecordField::Crc32,
+                RecordSource::DataDescriptor,
+                descriptor.crc_32_of_uncompressed_data() as u64,
+                cd.crc_32_of_uncompressed_data() as u64,
+            ),
+            (
+                RecordField::CompressedSize,
+                RecordSource::DataDescriptor,
+                descriptor.compressed_size(),
+                cd.compressed_size(),
+            ),
+            (
+                RecordField::UncompressedSize,
+                RecordSource::DataDescriptor,
+                descriptor.uncompressed_size(),
+                cd.uncompressed_size(),
+            ),
+        ]);
+    }
+
+    recorded
+        .into_iter()
+        .filter(|(_, _, value, expected)| value != expected)
+        .map(|(field, source, recorded, central_directory)| Discrepancy {
+            field,
+            source,
+            recorded,
+            central_directory,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitised(name: &str) -> (String, bool) {
+        let (path, rewritten) = sanitise_path(name).unwrap();
+        (path.to_str().unwrap().replace(std::path::MAIN_SEPARATOR, "/"), rewritten)
+    }
+
+    #[test]
+    fn sanitise_path_keeps_plain_names() {
+        assert_eq!(sanitised("a/b.txt"), ("a/b.txt".to_string(), false));
+        assert_eq!(sanitised("dir/"), ("dir".to_string(), false));
+    }
+
+    #[test]
+    fn sanitise_path_rejects_parent_directory() {
+        assert_eq!(sanitise_path("../x"), Err(UnsafePath::ParentDirectory));
+        assert_eq!(sanitise_path("a/../../x"), Err(UnsafePath::ParentDirectory));
+        assert_eq!(sanitise_path("a\\..\\x"), Err(UnsafePath::ParentDirectory));
+    }
+
+    #[test]
+    fn sanitise_path_strips_drive_letter() {
+        assert_eq!(sanitised("C:\\x"), ("x".to_string(), true));
+        assert_eq!(sanitised("c:/dir/x"), ("dir/x".to_string(), true));
+    }
+
+    #[test]
+    fn sanitise_path_strips_unc_prefix() {
+        assert_eq!(sanitised("\\\\server\\share\\x"), ("server/share/x".to_string(), true));
+    }
+
+    #[test]
+    fn sanitise_path_strips_leading_separator() {
+        assert_eq!(sanitised("/etc/passwd"), ("etc/passwd".to_string(), true));
+    }
+
+    #[test]
+    fn sanitise_path_drops_current_directory() {
+        assert_eq!(sanitised("a/./b"), ("a/b".to_string(), true));
+        assert_eq!(sanitised("a//b"), ("a/b".to_string(), true));
+    }
+
+    #[test]
+    fn sanitise_path_rejects_invalid_characters() {
+        assert_eq!(sanitise_path("a\0b"), Err(UnsafePath::InvalidCharacter('\0')));
+        assert_eq!(sanitise_path("file:stream"), Err(UnsafePath::InvalidCharacter(':')));
+        assert_eq!(sanitise_path("dir/a:stream"), Err(UnsafePath::InvalidCharacter(':')));
+    }
+
+    #[test]
+    fn sanitise_path_reads_single_letter_prefix_as_drive() {
+        /*
+        Windows opens a:stream as the path stream relative to drive A
+        */
+        assert_eq!(sanitised("a:stream"), ("stream".to_string(), true));
+        assert_eq!(sanitise_path("a:b:stream"), Err(UnsafePath::InvalidCharacter(':')));
+    }
+
+    #[test]
+    fn sanitise_path_rejects_empty_names() {
+        assert_eq!(sanitise_path(""), Err(UnsafePath::Empty));
+        assert_eq!(sanitise_path("/./"), Err(UnsafePath::Empty));
+        assert_eq!(sanitise_path("C:"), Err(UnsafePath::Empty));
+    }
+}
@@ -0,0 +1,1016 @@
+use bytes::{Bytes, BytesMut};
+use chrono::DateTime;
+use log::{debug, trace, warn};
+use rand::Rng;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE, RETRY_AFTER};
+use reqwest::tls::TlsInfo;
+use reqwest::{Client, Response, StatusCode};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{client, ZipSniperError};
+
+/*
+A span of bytes within the remote file, `start` inclusive and `end` exclusive.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/*
+Decides how a set of byte ranges is turned into HTTP requests.
+*/
+#[derive(Debug, Clone)]
+pub struct RangePlanner {
+    /*
+    Neighbouring ranges are merged when fewer than this many bytes lie between them.
+    */
+    pub max_gap: u64,
+    /*
+    Upper bound of the bytes pulled by a single range, larger ranges are split.
+    */
+    pub max_request: u64,
+    /*
+    Sends several ranges in one request and parses the multipart/byteranges reply.
+    */
+    pub multi_range: bool,
+    /*
+    Upper bound of the ranges sent in one multi-range request.
+    */
+    pub max_ranges_per_request: usize,
+}
+
+/*
+How requests failing for a transient reason are retried: server errors (5xx),
+408 and 429 replies, timeouts and dropped connections.
+*/
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /*
+    Retries of a single request, 0 sends every request once.
+    */
+    pub max_retries: u32,
+    /*
+    The delay before the first retry, doubled for every further one.
+    */
+    pub initial_backoff: Duration,
+    /*
+    Upper bound of the delay between two attempts. A `Retry-After` longer than
+    this fails the request instead of being cut short.
+    */
+    pub max_backoff: Duration,
+}
+
+/*
+Pulls byte ranges of a remote file, or of the volumes of a split archive laid
+end to end as if they were a single file.
+*/
+#[derive(Debug, Clone)]
+pub struct Fetcher {
+    client: Client,
+    volumes: Vec<Volume>,
+    planner: RangePlanner,
+    /*
+    The DER certificate the server presented to the first HTTPS request
+    */
+    server_certificate: OnceLock<Vec<u8>>,
+    retry_policy: RetryPolicy,
+    /*
+    Upper bound of the wait for the headers of a reply and for every chunk of
+    its body.
+    */
+    read_timeout: Option<Duration>,
+    /*
+    Requests sent again after a transient failure, shared by the clones
+    */
+    retries: Arc<AtomicU64>,
+}
+
+/*
+A single remote file of a split archive, holding the disk of the same number.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Volume {
+    pub path: String,
+    /*
+    Where the volume starts when all of them are laid end to end.
+    */
+    pub start: u64,
+    /*
+    The size of the volume, unknown for the last one.
+    */
+    pub length: Option<u64>,
+}
+
+/*
+A chunk of the remote file returned by the server
+*/
+#[derive(Debug)]
+struct Part {
+    start: u64,
+    data: Bytes,
+}
+
+impl ByteRange {
+    pub fn new(start: u64, length: u64) -> Self {
+        Self {
+            start,
+            end: start + length,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    fn header_value(&self) -> String {
+        format!("{}-{}", self.start, self.end - 1)
+    }
+}
+
+impl Default for RangePlanner {
+    fn default() -> Self {
+        Self {
+            max_gap: 64 * 1024,
+            max_request: 16 * 1024 * 1024,
+            multi_range: false,
+            max_ranges_per_request: 32,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        /*
+        The delay before retry number <attempt> + 1: the initial backoff doubled for
+        every earlier retry, capped at the maximum. Half of it is random, so clients
+        failing together do not retry together.
+        */
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+    }
+}
+
+impl RangePlanner {
+    pub fn plan(&self, ranges: &[ByteRange]) -> Vec<Vec<ByteRange>> {
+        /*
+        Turns the ranges into the requests that cover them, every request being a
+        list of ranges to put in its Range header.
+
+        Ranges larger than max_request are split into pieces first. The pieces are then
+        sorted and merged with their neighbour while the gap between them is at most
+        max_gap and the merged range stays within max_request. Without multi-range
+        support, every merged range is a request of its own.
+
+        # Arguments
+        * `ranges: &[ByteRange]` The ranges which need to be pulled, in any order
+        */
+        let max_request = self.max_request.max(1);
+        let mut pieces: Vec<ByteRange> = Vec::new();
+        for range in ranges.iter().filter(|range| !range.is_empty()) {
+            let mut start = range.start;
+            while start < range.end {
+                let end = range.end.min(start + max_request);
+                pieces.push(ByteRange { start, end });
+                start = end;
+            }
+        }
+        pieces.sort();
+
+        let mut merged: Vec<ByteRange> = Vec::new();
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last)
+                    if piece.start <= last.end.saturating_add(self.max_gap)
+                        && piece.end.max(last.end) - last.start <= max_request =>
+                {
+                    last.end = last.end.max(piece.end);
+                }
+                _ => merged.push(piece),
+            }
+        }
+
+        match self.multi_range {
+            true => merged
+                .chunks(self.max_ranges_per_request.max(1))
+                .map(|chunk| chunk.to_vec())
+                .collect(),
+            false => merged.into_iter().map(|range| vec![range]).collect(),
+        }
+    }
+}
+
+impl Volume {
+    pub fn split_paths(path: &str, parts: u32) -> Vec<String> {
+        /*
+        The URLs of the volumes of an archive split the way PKZIP and Info-ZIP do it,
+        archive.z01, archive.z02, ..., archive.zip. A query string is kept on every
+        volume. Without a .zip extension the volume number is appended instead.
+
+        # Arguments
+        * `path: &str` The URL of the last volume, archive.zip
+        * `parts: u32` The number of volumes before the last one
+        */
+        let (base, query) = match path.find('?') {
+            Some(position) => path.split_at(position),
+            None => (path, ""),
+        };
+        let stem = match base.len() >= 4 && base[base.len() - 4..].eq_ignore_ascii_case(".zip") {
+            true => &base[..base.len() - 4],
+            false => base,
+        };
+
+        let mut paths: Vec<String> = (1..=parts)
+            .map(|part| format!("{}.z{:02}{}", stem, part, query))
+            .collect();
+        paths.push(path.to_string());
+        paths
+    }
+
+    fn local_range(&self, range: &ByteRange) -> Option<ByteRange> {
+        /*
+        The part of <range> held by this volume, relative to the start of the volume
+        */
+        let end = match self.length {
+            Some(length) => range.end.min(self.start + length),
+            None => range.end,
+        };
+        let start = range.start.max(self.start);
+        match start < end {
+            true => Some(ByteRange {
+                start: start - self.start,
+                end: end - self.start,
+            }),
+            false => None,
+        }
+    }
+}
+
+impl Fetcher {
+    pub fn new(client: Client, path: String, planner: RangePlanner) -> Self {
+        Self {
+            client,
+            volumes: vec![Volume {
+                path,
+                start: 0,
+                length: None,
+            }],
+            planner,
+            server_certificate: OnceLock::new(),
+            retry_policy: RetryPolicy::default(),
+            read_timeout: None,
+            retries: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_read_timeout(mut self, read_timeout: Option<Duration>) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub async fn with_volumes(mut self, paths: Vec<String>) -> Result<Self, ZipSniperError> {
+        self.load_volumes(paths).await?;
+        Ok(self)
+    }
+
+    pub async fn load_volumes(&mut self, paths: Vec<String>) -> Result<(), ZipSniperError> {
+        /*
+        Turns the fetcher into one for a split archive. <paths> holds every volume in
+        disk order, the last one being the volume with the EOCD.
+
+        The size of every volume but the last is asked for with a one byte range
+        request, so the volumes can be laid end to end and a disk number and offset
+        turned into a single position.
+        */
+        let mut volumes = Vec::with_capacity(paths.len());
+        let mut start = 0;
+        let count = paths.len();
+        for (disk, path) in paths.into_iter().enumerate() {
+            let length = match disk + 1 < count {
+                true => Some(self.volume_length(&path).await?),
+                false => None,
+            };
+            debug!("Disk {} is {} (starting at {}, {:?} bytes)", disk, path, start, length);
+            volumes.push(Volume { path, start, length });
+            start += length.unwrap_or(0);
+        }
+
+        if !volumes.is_empty() {
+            self.volumes = volumes;
+        }
+        Ok(())
+    }
+
+    pub fn with_planner(mut self, planner: RangePlanner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        /*
+        The path of the last volume, which holds the EOCD
+        */
+        &self.last_volume().path
+    }
+
+    pub fn server_certificate(&self) -> Option<&[u8]> {
+        self.server_certificate.get().map(Vec::as_slice)
+    }
+
+    pub fn volumes(&self) -> &[Volume] {
+        &self.volumes
+    }
+
+    pub fn position(&self, disk: u32, offset: u64) -> Result<u64, ZipSniperError> {
+        /*
+        Turns an offset relative to the start of a disk into a position within the
+        volumes laid end to end. A single file is taken as is, whatever disk the
+        archive claims.
+        */
+        if self.volumes.len() == 1 {
+            return Ok(offset);
+        }
+        self.volumes
+            .get(disk as usize)
+            .map(|volume| volume.start + offset)
+            .ok_or(ZipSniperError::VolumeNotFound(disk))
+    }
+
+    pub async fn fetch_tail(&self, length: u64) -> Result<(u64, Bytes), ZipSniperError> {
+        /*
+        Grabs the last <length> bytes of the remote file, along with the absolute
+        offset of the first of them.
+        */
+        let volume = self.last_volume();
+        let mut parts = self
+            .send(&volume.path, format!("bytes=-{}", length), Part::from_response)
+            .await?;
+        match parts.len() {
+            1 => {
+                let part = parts.remove(0);
+                Ok((volume.start + part.start, part.data))
+            }
+            _ => Err(ZipSniperError::MalformedRangeResponse),
+        }
+    }
+
+    pub async fn fetch_range(&self, range: ByteRange) -> Result<Bytes, ZipSniperError> {
+        /*
+        Grabs a single range of the remote file
+        */
+        let mut data = self.fetch_ranges(&[range]).await?;
+        Ok(data.remove(0))
+    }
+
+    pub async fn fetch_ranges(&self, ranges: &[ByteRange]) -> Result<Vec<Bytes>, ZipSniperError> {
+        /*
+        Grabs several ranges of the remote file with as few requests as the planner
+        allows, then hands every range its own slice, in the order of <ranges>.
+
+        A server may answer a multi-range request with fewer parts than asked for, the
+        ranges it left out are pulled one by one.
+
+        With a split archive, every range is cut at the volume boundaries and each
+        volume is pulled on its own, the pieces are stitched back together after.
+        */
+        let mut parts: Vec<Part> = Vec::new();
+        for volume in self.volumes.iter() {
+            let local: Vec<ByteRange> = ranges.iter().filter_map(|range| volume.local_range(range)).collect();
+            if local.is_empty() {
+                continue;
+            }
+
+            let plan = self.planner.plan(&local);
+            debug!("Pulling {} ranges of {} with {} requests", local.len(), volume.path, plan.len());
+            for request in plan {
+                let received = self.fetch_request(&volume.path, &request).await?;
+                parts.extend(received.into_iter().map(|part| Part {
+                    start: volume.start + part.start,
+                    data: part.data,
+                }));
+            }
+        }
+        parts.sort_by_key(|part| part.start);
+
+        ranges
+            .iter()
+            .map(|range| {
+                Self::assemble(&parts, range).ok_or(ZipSniperError::RangeNotReturned {
+                    start: range.start,
+                    end: range.end,
+                })
+            })
+            .collect()
+    }
+
+    async fn fetch_request(&self, path: &str, request: &[ByteRange]) -> Result<Vec<Part>, ZipSniperError> {
+        /*
+        Sends a single request of the plan and returns the parts of the reply,
+        pulling the ranges a multi-range reply left out one by one.
+        */
+        let header = request
+            .iter()
+            .map(ByteRange::header_value)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut parts = self
+            .send(path, format!("bytes={}", header), Part::from_response)
+            .await?;
+
+        let missing: Vec<ByteRange> = request
+            .iter()
+            .filter(|range| Self::assemble(&parts, range).is_none())
+            .copied()
+            .collect();
+        if request.len() > 1 {
+            for range in missing {
+                trace!("Server left out the range {}, pulling it on its own", range.header_value());
+                let missing = self
+                    .send(path, format!("bytes={}", range.header_value()), Part::from_response)
+                    .await?;
+                parts.extend(missing);
+            }
+        }
+
+        Ok(parts)
+    }
+
+    async fn volume_length(&self, path: &str) -> Result<u64, ZipSniperError> {
+        /*
+        Asks for the first byte of a volume, the Content-Range of the reply names the
+        size of the whole volume. A server ignoring the Range header sends the whole
+        volume instead.
+        */
+        let read_length = |response: Response, read_timeout: Option<Duration>| async move {
+            let total = response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Part::parse_total_length);
+            match (response.status(), total) {
+                (StatusCode::PARTIAL_CONTENT, Some(total)) => Ok(total),
+                (StatusCode::PARTIAL_CONTENT, None) => Err(ZipSniperError::MalformedRangeResponse),
+                _ => Ok(read_body(response, read_timeout).await?.len() as u64),
+            }
+        };
+        self.send(path, String::from("bytes=0-0"), read_length).await
+    }
+
+    fn last_volume(&self) -> &Volume {
+        self.volumes.last().expect("a fetcher always has a volume")
+    }
+
+    async fn send<T, F, Fut>(&self, path: &str, range: String, read: F) -> Result<T, ZipSniperError>
+    where
+        F: Fn(Response, Option<Duration>) -> Fut,
+        Fut: Future<Output = Result<T, ZipSniperError>>,
+    {
+        /*
+        Sends a ranged GET and hands the reply to <read>. The request is sent again,
+        after a growing delay, as long as it fails for a transient reason and the
+        retry policy allows it. A 429 or 503 reply saying when to come back is
+        honoured, unless that is further away than the maximum backoff, in which case
+        the request fails right away rather than retrying too early.
+
+        # Arguments
+        * `path: &str` The URL of the volume
+        * `range: String` The value of the Range header
+        * `read: F` Reads the body of a successful reply, given the read timeout
+        */
+        let mut attempt = 0;
+        loop {
+            trace!("GET {} Range: {}", path, range);
+            let (outcome, retry_after) = match self.send_once(path, &range).await {
+                Ok(response) if response.status().is_success() => {
+                    self.record_server_certificate(&response);
+                    (read(response, self.read_timeout).await, None)
+                }
+                Ok(response) => {
+                    self.record_server_certificate(&response);
+                    let retry_after = Self::retry_after(&response);
+                    (Err(ZipSniperError::InvalidStatusCode(response.status())), retry_after)
+                }
+                Err(err) => (Err(err), None),
+            };
+
+            match outcome {
+                Err(err) if err.is_transient() && attempt < self.retry_policy.max_retries => {
+                    let delay = match retry_after {
+                        Some(retry_after) if retry_after > self.retry_policy.max_backoff => {
+                            warn!(
+                                "GET {} Range: {} failed ({}), the server asks to retry in {}s, longer than the maximum backoff of {}s, giving up",
+                                path,
+                                range,
+                                err,
+                                retry_after.as_secs(),
+                                self.retry_policy.max_backoff.as_secs()
+                            );
+                            return Err(err);
+                        }
+                        Some(retry_after) => {
+                            debug!("The server asks to retry in {}s", retry_after.as_secs());
+                            retry_after
+                        }
+                        None => self.retry_policy.backoff(attempt),
+                    };
+                    attempt += 1;
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    debug!(
+                        "GET {} Range: {} failed ({}), retry {} of {} in {:.1}s",
+                        path,
+                        range,
+                        err,
+                        attempt,
+                        self.retry_policy.max_retries,
+                        delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+
+    async fn send_once(&self, path: &str, range: &str) -> Result<Response, ZipSniperError> {
+        /*
+        Sends a ranged GET and waits for the headers of the reply, for no longer than
+        the read timeout
+        */
+        let request = self.client.get(path).header(RANGE, range).send();
+        let response = match self.read_timeout {
+            Some(read_timeout) => tokio::time::timeout(read_timeout, request)
+                .await
+                .map_err(|_| ZipSniperError::ReadTimedOut(read_timeout))?,
+            None => request.await,
+        };
+        response.map_err(ZipSniperError::HttpError)
+    }
+
+    fn retry_after(response: &Response) -> Option<Duration> {
+        /*
+        Retry-After: 120
+        Retry-After: Wed, 21 Oct 2015 07:28:00 GMT
+        Only read from the replies it is meant for, 429 and 503.
+        */
+        if !matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+            return None;
+        }
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Self::parse_retry_after(value, now)
+    }
+
+    fn parse_retry_after(value: &str, now: u64) -> Option<Duration> {
+        /*
+        Reads a Retry-After value, either a number of seconds or an HTTP-date. A date
+        already past means no wait at all.
+
+        # Arguments
+        * `value: &str` The value of the header
+        * `now: u64` The current time, in seconds since the Unix epoch
+        */
+        let value = value.trim();
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+
+        let date = DateTime::parse_from_rfc2822(value).ok()?.timestamp();
+        Some(Duration::from_secs(date.saturating_sub(now as i64).max(0) as u64))
+    }
+
+    fn record_server_certificate(&self, response: &Response) {
+        /*
+        Keeps the certificate of the first HTTPS reply, whatever its status, so it is
+        on record even when the run fails later on. A later reply presenting a
+        different certificate is warned about.
+        */
+        let Some(certificate) = response
+            .extensions()
+            .get::<TlsInfo>()
+            .and_then(TlsInfo::peer_certificate)
+        else {
+            return;
+        };
+        if self.server_certificate.set(certificate.to_vec()).is_ok() {
+            debug!("{} presented its certificate", response.url());
+            return;
+        }
+        let recorded = self.server_certificate.get().expect("the certificate was recorded");
+        if recorded != certificate {
+            warn!(
+                "{} presented the certificate {}, the first request was answered with {}",
+                response.url(),
+                client::certificate_fingerprint(certificate),
+                client::certificate_fingerprint(recorded)
+            );
+        }
+    }
+
+    fn assemble(parts: &[Part], range: &ByteRange) -> Option<Bytes> {
+        /*
+        Cuts <range> out of the parts returned by the server. A range held by a single
+        part is sliced out of it without copying, a range spread over several
+        consecutive parts is stitched back together.
+        */
+        if range.is_empty() {
+            return Some(Bytes::new());
+        }
+
+        let contains = |part: &&Part| {
+            part.start <= range.start && part.start + part.data.len() as u64 >= range.end
+        };
+        if let Some(part) = parts.iter().find(contains) {
+            let start = (range.start - part.start) as usize;
+            return Some(part.data.slice(start..start + range.len() as usize));
+        }
+
+        let mut stitched = BytesMut::with_capacity(range.len() as usize);
+        let mut position = range.start;
+        while position < range.end {
+            let part = parts.iter().find(|part| {
+                part.start <= position && part.start + part.data.len() as u64 > position
+            })?;
+            let start = (position - part.start) as usize;
+            let end = (part.data.len() as u64).min(range.end - part.start) as usize;
+            stitched.extend_from_slice(&part.data[start..end]);
+            position = part.start + end as u64;
+        }
+        Some(stitched.freeze())
+    }
+}
+
+async fn read_body(mut response: Response, read_timeout: Option<Duration>) -> Result<Bytes, ZipSniperError> {
+    /*
+    Reads the whole body of a reply. With a read timeout, the body is read chunk by
+    chunk and a server which stays silent for longer than that is given up on.
+    */
+    let Some(read_timeout) = read_timeout else {
+        return response.bytes().await.map_err(ZipSniperError::HttpError);
+    };
+
+    let mut body = BytesMut::new();
+    loop {
+        match tokio::time::timeout(read_timeout, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => body.extend_from_slice(&chunk),
+            Ok(Ok(None)) => return Ok(body.freeze()),
+            Ok(Err(err)) => return Err(ZipSniperError::HttpError(err)),
+            Err(_) => return Err(ZipSniperError::ReadTimedOut(read_timeout)),
+        }
+    }
+}
+
+impl Part {
+    async fn from_response(response: Response, read_timeout: Option<Duration>) -> Result<Vec<Part>, ZipSniperError> {
+        /*
+        Splits the reply to a range request into the parts of the remote file it holds.
+
+        200: the server ignored the Range header and sent the whole file
+        206 with Content-Range: a single part
+        206 with multipart/byteranges: every body part carries its own Content-Range
+        */
+        let status = response.status();
+        let content_range = response
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse_content_range);
+        let boundary = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::multipart_boundary);
+        let body = read_body(response, read_timeout).await?;
+
+        match (status, content_range, boundary) {
+            (StatusCode::PARTIAL_CONTENT, _, Some(boundary)) => Self::parse_multipart(body, &boundary),
+            (StatusCode::PARTIAL_CONTENT, Some((start, _)), None) => Ok(vec![Part { start, data: body }]),
+            (StatusCode::PARTIAL_CONTENT, None, None) => Err(ZipSniperError::MalformedRangeResponse),
+            _ => Ok(vec![Part { start: 0, data: body }]),
+        }
+    }
+
+    fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+        /*
+        Content-Range: bytes 200-1000/67589
+        Returns the first and last byte position.
+        */
+        let range = value.trim().strip_prefix("bytes")?.trim();
+        let (range, _total) = range.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some((start.trim().parse().ok()?, end.trim().parse().ok()?))
+    }
+
+    fn parse_total_length(value: &str) -> Option<u64> {
+        /*
+        Content-Range: bytes 0-0/67589
+        Returns the size of the whole file, unless the server left it out (*).
+        */
+        let (_, total) = value.trim().split_once('/')?;
+        total.trim().parse().ok()
+    }
+
+    fn multipart_boundary(value: &str) -> Option<String> {
+        /*
+        Content-Type: multipart/byteranges; boundary=3d6b6a416f9b5
+        */
+        let mut fields = value.split(';');
+        if !fields.next()?.trim().eq_ignore_ascii_case("multipart/byteranges") {
+            return None;
+        }
+        fields
+            .filter_map(|field| field.trim().split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+            .map(|(_, boundary)| boundary.trim().trim_matches('"').to_string())
+    }
+
+    fn parse_multipart(body: Bytes, boundary: &str) -> Result<Vec<Part>, ZipSniperError> {
+        /*
+        Every body part starts with --<boundary>, followed by its headers and an empty
+        line. The length of the part data is taken from its Content-Range header, so a
+        boundary showing up inside of binary data does no harm.
+        */
+        let delimiter = format!("--{}", boundary);
+        let mut parts = Vec::new();
+        let mut position = 0;
+
+        loop {
+            let start = Self::find(&body, delimiter.as_bytes(), position)
+                .ok_or(ZipSniperError::MalformedRangeResponse)?;
+            position = start + delimiter.len();
+            if body[position..].starts_with(b"--") {
+                break;
+            }
+
+            let headers_end = Self::find(&body, b"\r\n\r\n", position)
+                .ok_or(ZipSniperError::MalformedRangeResponse)?;
+            let headers = String::from_utf8_lossy(&body[position..headers_end]);
+            let (first, last) = headers
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-range"))
+                .and_then(|(_, value)| Self::parse_content_range(value))
+                .ok_or(ZipSniperError::MalformedRangeResponse)?;
+
+            let data_start = headers_end + 4;
+            let data_end = last
+                .checked_add(1)
+                .and_then(|end| end.checked_sub(first))
+                .and_then(|length| data_start.checked_add(length as usize))
+                .filter(|data_end| *data_end <= body.len())
+                .ok_or(ZipSniperError::MalformedRangeResponse)?;
+            parts.push(Part {
+                start: first,
+                data: body.slice(data_start..data_end),
+            });
+            position = data_end;
+        }
+
+        Ok(parts)
+    }
+
+    fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+        haystack
+            .get(from..)?
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| position + from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    fn planner(max_gap: u64, max_request: u64, multi_range: bool, max_ranges_per_request: usize) -> RangePlanner {
+        RangePlanner {
+            max_gap,
+            max_request,
+            multi_range,
+            max_ranges_per_request,
+        }
+    }
+
+    fn part(start: u64, data: &'static [u8]) -> Part {
+        Part {
+            start,
+            data: Bytes::from_static(data),
+        }
+    }
+
+    fn policy(initial: u64, max: u64) -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(initial),
+            max_backoff: Duration::from_millis(max),
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_with_half_of_it_random() {
+        let policy = policy(100, 10_000);
+        for (attempt, exponential) in [(0, 100), (1, 200), (2, 400), (3, 800)] {
+            for _ in 0..50 {
+                let backoff = policy.backoff(attempt);
+                assert!(backoff >= Duration::from_millis(exponential / 2), "{:?}", backoff);
+                assert!(backoff <= Duration::from_millis(exponential), "{:?}", backoff);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_at_the_maximum() {
+        let policy = policy(100, 1000);
+        for attempt in [4, 10, 31, 32, u32::MAX] {
+            for _ in 0..50 {
+                let backoff = policy.backoff(attempt);
+                assert!(backoff >= Duration::from_millis(500), "{:?}", backoff);
+                assert!(backoff <= Duration::from_millis(1000), "{:?}", backoff);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_jitter_varies() {
+        let policy = policy(1000, 1000);
+        let first = policy.backoff(0);
+        assert!((0..50).any(|_| policy.backoff(0) != first));
+    }
+
+    #[test]
+    fn retry_after_reads_seconds() {
+        assert_eq!(Fetcher::parse_retry_after("120", 0), Some(Duration::from_secs(120)));
+        assert_eq!(Fetcher::parse_retry_after(" 0 ", 0), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_reads_http_date() {
+        let date = 1_445_412_480;
+        let value = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(
+            Fetcher::parse_retry_after(value, date - 90),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(Fetcher::parse_retry_after(value, date), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_date_in_the_past_means_no_wait() {
+        let value = "Wed, 21 Oct 2015 07:28:00 GMT";
+        assert_eq!(
+            Fetcher::parse_retry_after(value, 1_445_412_480 + 3600),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn retry_after_rejects_garbage() {
+        assert_eq!(Fetcher::parse_retry_after("soon", 0), None);
+        assert_eq!(Fetcher::parse_retry_after("-5", 0), None);
+        assert_eq!(Fetcher::parse_retry_after("", 0), None);
+    }
+
+    #[test]
+    fn plan_merges_ranges_within_gap() {
+        let planner = planner(10, 1000, false, 32);
+        let requests = planner.plan(&[range(50, 60), range(0, 10), range(15, 20)]);
+        assert_eq!(requests, vec![vec![range(0, 20)], vec![range(50, 60)]]);
+    }
+
+    #[test]
+    fn plan_merges_overlapping_ranges() {
+        let planner = planner(0, 1000, false, 32);
+        let requests = planner.plan(&[range(0, 10), range(5, 8), range(10, 20)]);
+        assert_eq!(requests, vec![vec![range(0, 20)]]);
+    }
+
+    #[test]
+    fn plan_drops_empty_ranges() {
+        let planner = planner(0, 1000, false, 32);
+        assert!(planner.plan(&[range(5, 5)]).is_empty());
+    }
+
+    #[test]
+    fn plan_splits_at_max_request() {
+        let planner = planner(0, 10, false, 32);
+        let requests = planner.plan(&[range(0, 25)]);
+        assert_eq!(requests, vec![vec![range(0, 10)], vec![range(10, 20)], vec![range(20, 25)]]);
+    }
+
+    #[test]
+    fn plan_does_not_merge_past_max_request() {
+        let planner = planner(10, 10, false, 32);
+        let requests = planner.plan(&[range(0, 6), range(8, 14)]);
+        assert_eq!(requests, vec![vec![range(0, 6)], vec![range(8, 14)]]);
+    }
+
+    #[test]
+    fn plan_chunks_multi_range_requests() {
+        let planner = planner(0, 1000, true, 2);
+        let requests = planner.plan(&[range(0, 1), range(10, 11), range(20, 21), range(30, 31), range(40, 41)]);
+        assert_eq!(
+            requests,
+            vec![
+                vec![range(0, 1), range(10, 11)],
+                vec![range(20, 21), range(30, 31)],
+                vec![range(40, 41)],
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_multipart_reads_every_part() {
+        let body = Bytes::from_static(
+            b"--sep\r\nContent-Type: application/zip\r\nContent-Range: bytes 0-3/100\r\n\r\nPK--\r\n\
+              --sep\r\nContent-Range: bytes 96-99/100\r\n\r\nabcd\r\n--sep--\r\n",
+        );
+        let parts = Part::parse_multipart(body, "sep").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!((parts[0].start, parts[0].data.as_ref()), (0, &b"PK--"[..]));
+        assert_eq!((parts[1].start, parts[1].data.as_ref()), (96, &b"abcd"[..]));
+    }
+
+    #[test]
+    fn parse_multipart_rejects_inverted_range() {
+        let body = Bytes::from_static(b"--sep\r\nContent-Range: bytes 10-5/100\r\n\r\nabcdef\r\n--sep--\r\n");
+        assert!(matches!(
+            Part::parse_multipart(body, "sep"),
+            Err(ZipSniperError::MalformedRangeResponse)
+        ));
+    }
+
+    #[test]
+    fn parse_multipart_rejects_truncated_part() {
+        let body = Bytes::from_static(b"--sep\r\nContent-Range: bytes 0-99/100\r\n\r\nabcd\r\n--sep--\r\n");
+        assert!(matches!(
+            Part::parse_multipart(body, "sep"),
+            Err(ZipSniperError::MalformedRangeResponse)
+        ));
+    }
+
+    #[test]
+    fn parse_multipart_rejects_missing_content_range() {
+        let body = Bytes::from_static(b"--sep\r\nContent-Type: text/plain\r\n\r\nabcd\r\n--sep--\r\n");
+        assert!(matches!(
+            Part::parse_multipart(body, "sep"),
+            Err(ZipSniperError::MalformedRangeResponse)
+        ));
+    }
+
+    #[test]
+    fn assemble_slices_single_part() {
+        let parts = [part(10, b"abcdefgh")];
+        assert_eq!(Fetcher::assemble(&parts, &range(12, 15)).unwrap().as_ref(), b"cde");
+    }
+
+    #[test]
+    fn assemble_stitches_consecutive_parts() {
+        let parts = [part(20, b"klmn"), part(10, b"abcd"), part(14, b"efghij")];
+        assert_eq!(Fetcher::assemble(&parts, &range(12, 22)).unwrap().as_ref(), b"cdefghijkl");
+    }
+
+    #[test]
+    fn assemble_fails_on_missing_bytes() {
+        let parts = [part(0, b"abcd"), part(6, b"ghij")];
+        assert!(Fetcher::assemble(&parts, &range(2, 8)).is_none());
+    }
+
+    #[test]
+    fn assemble_returns_empty_range() {
+        assert_eq!(Fetcher::assemble(&[], &range(5, 5)).unwrap(), Bytes::new());
+    }
+}
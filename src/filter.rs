@@ -0,0 +1,102 @@
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+
+/*
+A single include or exclude pattern, matched against the entry name.
+*/
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+/*
+Selects entries by name. An entry is selected when it matches at least one
+include pattern (or there are none) and no exclude pattern.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct EntryFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+/*
+Why an entry was left out by an `EntryFilter`.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterResult {
+    Selected,
+    NotIncluded,
+    Excluded,
+}
+
+#[derive(Debug)]
+pub enum FilterError {
+    InvalidGlob(globset::Error),
+    InvalidRegex(regex::Error),
+}
+
+impl std::error::Error for FilterError {}
+
+impl std::fmt::Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FilterError::InvalidGlob(err) => write!(f, "Invalid glob: {}", err),
+            FilterError::InvalidRegex(err) => write!(f, "Invalid regex: {}", err),
+        }
+    }
+}
+
+impl Pattern {
+    pub fn glob(pattern: &str) -> Result<Self, FilterError> {
+        /*
+        A `*` also matches the path separator, so `*.txt` matches text files in
+        every directory.
+        */
+        let glob = Glob::new(pattern).map_err(FilterError::InvalidGlob)?;
+        Ok(Pattern::Glob(glob.compile_matcher()))
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, FilterError> {
+        let regex = Regex::new(pattern).map_err(FilterError::InvalidRegex)?;
+        Ok(Pattern::Regex(regex))
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob.is_match(name),
+            Pattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn include(mut self, pattern: Pattern) -> Self {
+        self.include.push(pattern);
+        self
+    }
+
+    pub fn exclude(mut self, pattern: Pattern) -> Self {
+        self.exclude.push(pattern);
+        self
+    }
+
+    pub fn check(&self, name: &str) -> FilterResult {
+        if self.exclude.iter().any(|pattern| pattern.is_match(name)) {
+            return FilterResult::Excluded;
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.is_match(name)) {
+            return FilterResult::NotIncluded;
+        }
+
+        FilterResult::Selected
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.check(name) == FilterResult::Selected
+    }
+}
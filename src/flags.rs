@@ -0,0 +1,112 @@
+use bitflags::{bitflags, Flags};
+
+bitflags! {
+    /*
+    The general purpose bit flag of a local file header or CD record (APPNOTE 4.4.4).
+
+    Bits without a name are reserved, they are kept rather than dropped.
+    */
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct GeneralPurposeFlags: u16 {
+        /*
+        Bit 0: the entry is encrypted.
+        */
+        const ENCRYPTED = 0x0001;
+        /*
+        Bit 1: compression option, its meaning depends on the compression method.
+        */
+        const COMPRESSION_OPTION_1 = 0x0002;
+        /*
+        Bit 2: compression option, its meaning depends on the compression method.
+        */
+        const COMPRESSION_OPTION_2 = 0x0004;
+        /*
+        Bit 3: the CRC-32 and sizes follow the data in a data descriptor.
+        */
+        const DATA_DESCRIPTOR = 0x0008;
+        /*
+        Bit 4: reserved for enhanced deflating.
+        */
+        const ENHANCED_DEFLATION = 0x0010;
+        /*
+        Bit 5: the entry holds compressed patched data.
+        */
+        const PATCHED_DATA = 0x0020;
+        /*
+        Bit 6: the entry uses strong encryption.
+        */
+        const STRONG_ENCRYPTION = 0x0040;
+        /*
+        Bit 11: the name and comment are UTF-8 (language encoding flag).
+        */
+        const UTF8 = 0x0800;
+        /*
+        Bit 13: the values of the local header are masked, as the Central
+        Directory is encrypted.
+        */
+        const MASKED_LOCAL_HEADERS = 0x2000;
+
+        const _ = !0;
+    }
+}
+
+/*
+The compression level a deflating writer used, from bits 1 and 2.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeflateOption {
+    Normal,
+    Maximum,
+    Fast,
+    SuperFast,
+}
+
+impl GeneralPurposeFlags {
+    pub fn deflate_option(&self) -> DeflateOption {
+        /*
+        Only meaningful for entries compressed with deflate or deflate64
+        */
+        match (
+            self.contains(Self::COMPRESSION_OPTION_2),
+            self.contains(Self::COMPRESSION_OPTION_1),
+        ) {
+            (false, false) => DeflateOption::Normal,
+            (false, true) => DeflateOption::Maximum,
+            (true, false) => DeflateOption::Fast,
+            (true, true) => DeflateOption::SuperFast,
+        }
+    }
+}
+
+impl std::fmt::Display for GeneralPurposeFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        /*
+        The names of the bits which are set separated by a pipe, followed by the
+        reserved bits which are set
+        */
+        let mut names: Vec<String> = self.iter_names().map(|(name, _)| name.to_string()).collect();
+        let named = Self::FLAGS
+            .iter()
+            .filter(|flag| flag.is_named())
+            .fold(0, |bits, flag| bits | flag.value().bits());
+        if self.bits() & !named != 0 {
+            names.push(format!("{:#06x}", self.bits() & !named));
+        }
+
+        match names.is_empty() {
+            true => write!(f, "-"),
+            false => write!(f, "{}", names.join(" | ")),
+        }
+    }
+}
+
+impl std::fmt::Display for DeflateOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DeflateOption::Normal => write!(f, "normal"),
+            DeflateOption::Maximum => write!(f, "maximum"),
+            DeflateOption::Fast => write!(f, "fast"),
+            DeflateOption::SuperFast => write!(f, "super fast"),
+        }
+    }
+}
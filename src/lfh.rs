@@ -0,0 +1,389 @@
+use bytes::{Buf, Bytes};
+
+use crate::compression::CompressionMethod;
+use crate::extra::{ExtraField, ExtraFields, Zip64Values};
+use crate::flags::GeneralPurposeFlags;
+use crate::timestamp::{EntryTimes, Timestamp};
+
+struct FieldMetaData {
+    size: usize,
+    offset: usize,
+}
+
+/*
+A local file header, which sits directly in front of the compressed data
+of every entry.
+*/
+#[derive(Debug)]
+pub struct Lfh {
+    binary: Bytes,
+    offset: u64,
+}
+
+#[derive(Debug)]
+pub enum LfhError {
+    NotValidBinary,
+    InvalidUTF8ByteVector,
+    Truncated,
+}
+
+impl std::error::Error for LfhError {}
+
+impl std::fmt::Display for LfhError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LfhError::NotValidBinary => write!(f, "Local file header signature not found"),
+            LfhError::InvalidUTF8ByteVector => write!(f, "Invalid UTF-8 byte vector"),
+            LfhError::Truncated => write!(f, "Local file header is truncated"),
+        }
+    }
+}
+
+impl FieldMetaData {
+    fn slice_range(&self) -> std::ops::Range<usize> {
+        self.offset..(self.offset + self.size)
+    }
+}
+
+impl Lfh {
+    /*
+    This checksum signifies the start of a local file header
+    */
+    const LFH_CHECKSUM: &'static [u8] = &[0x50, 0x4b, 0x03, 0x04];
+
+    /*
+    Size of the fixed portion of a local file header, before the
+    file name and extra field.
+    */
+    pub const HEADER_SIZE: usize = 30;
+
+    pub fn parse(offset: u64, binary: Bytes) -> Result<Self, LfhError> {
+        /*
+        Builds a local file header from the start of the supplied buffer. The buffer
+        may run past the end of the header, the surplus is sliced off.
+
+        # Arguments
+        * `offset: u64` The absolute offset of the header within the remote file
+        * `binary: Bytes` The bytes starting at that offset
+        */
+        if binary.len() < Self::HEADER_SIZE {
+            return Err(LfhError::Truncated);
+        }
+        if &binary[0..4] != Self::LFH_CHECKSUM {
+            return Err(LfhError::NotValidBinary);
+        }
+
+        let mut lfh = Self { binary, offset };
+        let header_length = lfh.header_length();
+        if lfh.binary.len() < header_length {
+            return Err(LfhError::Truncated);
+        }
+        lfh.binary.truncate(header_length);
+        Ok(lfh)
+    }
+
+    pub fn required_length(binary: &[u8]) -> Option<usize> {
+        /*
+        Returns the full length of the header starting at <binary>, as soon as the
+        fixed portion is available.
+        */
+        if binary.len() < Self::HEADER_SIZE {
+            return None;
+        }
+        let mut lengths = &binary[26..30];
+        Some(Self::HEADER_SIZE + lengths.get_u16_le() as usize + lengths.get_u16_le() as usize)
+    }
+
+    pub fn offset(&self) -> u64 {
+        /*
+        Absolute offset of the header within the remote file
+        */
+        self.offset
+    }
+
+    pub fn header_length(&self) -> usize {
+        /*
+        Description: Length of the whole header
+        30 + n + m
+        */
+        Self::HEADER_SIZE + self.file_name_length() as usize + self.extra_field_length() as usize
+    }
+
+    pub fn data_offset(&self) -> u64 {
+        /*
+        Absolute offset of the first byte of compressed data
+        */
+        self.offset + self.header_length() as u64
+    }
+
+    pub fn minimun_version_needed_to_extract(&self) -> u16 {
+        /*
+        Description: Version needed to extract (minimum)
+        Offset: 4
+        Size: 2
+        */
+        let metadata = FieldMetaData { offset: 4, size: 2 }.slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn general_purpose_bit_flag(&self) -> u16 {
+        /*
+        Description: General purpose bit flag
+        Offset: 6
+        Size: 2
+        */
+        let metadata = FieldMetaData { offset: 6, size: 2 }.slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn compression_method(&self) -> u16 {
+        /*
+        Description: Compression method
+        Offset: 8
+        Size: 2
+        */
+        let metadata = FieldMetaData { offset: 8, size: 2 }.slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn flags(&self) -> GeneralPurposeFlags {
+        GeneralPurposeFlags::from_bits_retain(self.general_purpose_bit_flag())
+    }
+
+    pub fn compression(&self) -> CompressionMethod {
+        CompressionMethod::from(self.compression_method())
+    }
+
+    pub fn file_last_modification_time(&self) -> u16 {
+        /*
+        Description: File last modification time
+        Offset: 10
+        Size: 2
+        */
+        let metadata = FieldMetaData {
+            offset: 10,
+            size: 2,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn file_last_modification_date(&self) -> u16 {
+        /*
+        Description: File last modification date
+        Offset: 12
+        Size: 2
+        */
+        let metadata = FieldMetaData {
+            offset: 12,
+            size: 2,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn times(&self) -> EntryTimes {
+        /*
+        The decoded modification, access and creation times, taken from the most
+        precise source available
+        */
+        EntryTimes::new(
+            self.file_last_modification_date(),
+            self.file_last_modification_time(),
+            self.extra_fields(),
+        )
+    }
+
+    pub fn last_modification(&self) -> Option<Timestamp> {
+        self.times().modified
+    }
+
+    pub fn crc_32_of_uncompressed_data(&self) -> u32 {
+        /*
+        Description: CRC-32 of uncompressed data
+        Offset: 14
+        Size: 4
+        */
+        let metadata = FieldMetaData {
+            offset: 14,
+            size: 4,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u32_le()
+    }
+
+    pub fn compressed_size(&self) -> u64 {
+        /*
+        Description: Compressed size (or 0xffffffff for ZIP64)
+        Offset: 18
+        Size: 4
+        64-bit: ZIP64 extended information extra field
+        */
+        let metadata = FieldMetaData {
+            offset: 18,
+            size: 4,
+        }
+        .slice_range();
+        let compressed_size = self.binary.slice(metadata).get_u32_le();
+
+        self.zip64_values()
+            .compressed_size
+            .unwrap_or(compressed_size as u64)
+    }
+
+    pub fn uncompressed_size(&self) -> u64 {
+        /*
+        Description: Uncompressed size (or 0xffffffff for ZIP64)
+        Offset: 22
+        Size: 4
+        64-bit: ZIP64 extended information extra field
+        */
+        let metadata = FieldMetaData {
+            offset: 22,
+            size: 4,
+        }
+        .slice_range();
+        let uncompressed_size = self.binary.slice(metadata).get_u32_le();
+
+        self.zip64_values()
+            .uncompressed_size
+            .unwrap_or(uncompressed_size as u64)
+    }
+
+    pub fn file_name_length(&self) -> u16 {
+        /*
+        Description: File name length (n)
+        Offset: 26
+        Size: 2
+        */
+        let metadata = FieldMetaData {
+            offset: 26,
+            size: 2,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn extra_field_length(&self) -> u16 {
+        /*
+        Description: Extra field length (m)
+        Offset: 28
+        Size: 2
+        */
+        let metadata = FieldMetaData {
+            offset: 28,
+            size: 2,
+        }
+        .slice_range();
+        self.binary.slice(metadata).get_u16_le()
+    }
+
+    pub fn file_name(&self) -> Result<String, LfhError> {
+        /*
+        Description: File name
+        Offset: 30
+        Size: n
+        */
+        let metadata = FieldMetaData {
+            offset: Self::HEADER_SIZE,
+            size: self.file_name_length() as usize,
+        }
+        .slice_range();
+        String::from_utf8(self.binary.slice(metadata).to_vec())
+            .map_err(|_| LfhError::InvalidUTF8ByteVector)
+    }
+
+    pub fn extra_field(&self) -> Bytes {
+        /*
+        Description: Extra field
+        Offset: 30 + n
+        Size: m
+        */
+        let metadata = FieldMetaData {
+            offset: Self::HEADER_SIZE + self.file_name_length() as usize,
+            size: self.extra_field_length() as usize,
+        }
+        .slice_range();
+        self.binary.slice(metadata)
+    }
+
+    pub fn extra_fields(&self) -> ExtraFields {
+        /*
+        Iterates over the typed records held within the extra field
+        */
+        ExtraFields::new(self.extra_field())
+    }
+
+    fn zip64_values(&self) -> Zip64Values {
+        /*
+        The ZIP64 extended information of a local header holds both the uncompressed
+        and the compressed size, each of them only taken over when the matching
+        header field is saturated.
+        */
+        let zip64 = self.extra_fields().find_map(|field| match field {
+            ExtraField::Zip64ExtendedInformation(zip64) => Some(zip64),
+            _ => None,
+        });
+
+        match zip64 {
+            Some(zip64) => {
+                let saturated = |offset: usize| {
+                    self.binary.slice(offset..offset + 4).get_u32_le() == 0xFFFFFFFF
+                };
+                zip64.resolve_local(saturated(22), saturated(18))
+            }
+            None => Zip64Values::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(compressed_size: u32, uncompressed_size: u32, extra: &[u8]) -> Lfh {
+        let mut binary = vec![0x50, 0x4b, 0x03, 0x04, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        binary.extend_from_slice(&compressed_size.to_le_bytes());
+        binary.extend_from_slice(&uncompressed_size.to_le_bytes());
+        binary.extend_from_slice(&1u16.to_le_bytes());
+        binary.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+        binary.push(b'a');
+        binary.extend_from_slice(extra);
+        Lfh::parse(0, Bytes::from(binary)).unwrap()
+    }
+
+    fn zip64_extra(uncompressed_size: u64, compressed_size: u64) -> Vec<u8> {
+        let mut extra = vec![0x01, 0x00, 16, 0];
+        extra.extend_from_slice(&uncompressed_size.to_le_bytes());
+        extra.extend_from_slice(&compressed_size.to_le_bytes());
+        extra
+    }
+
+    #[test]
+    fn zip64_sizes_both_saturated() {
+        let lfh = header(0xFFFFFFFF, 0xFFFFFFFF, &zip64_extra(0x1_0000_0000, 0x2_0000_0000));
+        assert_eq!(lfh.uncompressed_size(), 0x1_0000_0000);
+        assert_eq!(lfh.compressed_size(), 0x2_0000_0000);
+    }
+
+    #[test]
+    fn zip64_compressed_size_only_saturated() {
+        let lfh = header(0xFFFFFFFF, 1000, &zip64_extra(1000, 0x2_0000_0000));
+        assert_eq!(lfh.uncompressed_size(), 1000);
+        assert_eq!(lfh.compressed_size(), 0x2_0000_0000);
+    }
+
+    #[test]
+    fn zip64_uncompressed_size_only_saturated() {
+        let lfh = header(1000, 0xFFFFFFFF, &zip64_extra(0x1_0000_0000, 1000));
+        assert_eq!(lfh.uncompressed_size(), 0x1_0000_0000);
+        assert_eq!(lfh.compressed_size(), 1000);
+    }
+
+    #[test]
+    fn zip64_sizes_not_saturated() {
+        let lfh = header(10, 20, &zip64_extra(0x1_0000_0000, 0x2_0000_0000));
+        assert_eq!(lfh.uncompressed_size(), 20);
+        assert_eq!(lfh.compressed_size(), 10);
+    }
+}
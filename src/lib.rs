@@ -1,153 +1,1085 @@
-use bytes::{Buf, Bytes};
-use reqwest::{Client, Response, StatusCode, Proxy};
+#![allow(non_snake_case)]
+
+use bytes::{Bytes, BytesMut};
+use reqwest::StatusCode;
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result as FmtResult};
-use log::{debug, error, info, trace, Level, LevelFilter};
-use tokio_socks::{tcp::Socks5Stream, Error as SocksError, TargetAddr};
-use tokio_tungstenite::client_async;
-
+use std::path::Path;
+use log::{debug, info, trace, warn};
 
-mod cd;
-mod eocd;
+pub mod attributes;
+pub mod cd;
+pub mod client;
+pub mod compression;
+pub mod cookie;
+pub mod descriptor;
+pub mod encoding;
+pub mod eocd;
+pub mod extra;
+pub mod extract;
+pub mod fetch;
+pub mod filter;
+pub mod flags;
+pub mod lfh;
+pub mod map;
+pub mod timestamp;
 
 pub struct ZipSniper {
     path: String,
-    client: reqwest::Client,
+    fetcher: fetch::Fetcher,
+    name_encoding: encoding::NameEncoding,
+}
+
+/*
+The local structures and the compressed data of an entry, as pulled from the remote file
+*/
+struct EntryData {
+    lfh: lfh::Lfh,
+    descriptor: Option<descriptor::DataDescriptor>,
+    data: Bytes,
 }
 
 impl ZipSniper {
-    pub fn new(path: String, proxy_url: Option<String> ) -> Self {
-        Self {
+    /*
+    Bytes pulled beyond the local file header length announced by the CD record,
+    as local headers often carry a larger extra field than their CD record.
+    */
+    const LOCAL_HEADER_SLACK: u64 = 64;
+
+    /*
+    Bulk extraction pulls the compressed data of this many bytes worth of entries
+    at a time, so memory use stays bounded.
+    */
+    const EXTRACT_BATCH_SIZE: u64 = 64 * 1024 * 1024;
+
+    /*
+    Bytes searched in front of the expected start of a Central Directory which is not
+    where the EOCD says it is
+    */
+    const CENTRAL_DIRECTORY_SEARCH_WINDOW: u64 = 4096;
+
+    /*
+    The furthest the EOCD can be from the end of the archive is a comment of 65535
+    bytes, the ZIP64 EOCD record and locator in front of it are pulled along
+    */
+    const EOCD_SEARCH_LIMIT: u64 = (eocd::Eocd::EOCD_32_SIZE
+        + 0xFFFF
+        + eocd::Eocd64Locator::SIZE
+        + eocd::Eocd::EOCD_64_SIZE) as u64;
+
+    /*
+    The EOCD search window grows by this factor every round trip
+    */
+    const EOCD_SEARCH_GROWTH: u64 = 8;
+
+    pub fn new(path: String, proxy_url: Option<String>) -> Result<Self, ZipSniperError> {
+        /*
+        # Arguments
+        * `path: String` The URL of the archive
+        * `proxy_url: Option<String>` The proxy to route every request through
+        */
+        let config = client::ClientConfig {
+            proxy: client::ProxyConfig {
+                url: proxy_url,
+                ..client::ProxyConfig::default()
+            },
+            ..client::ClientConfig::default()
+        };
+        Self::from_client_config(path, config)
+    }
+
+    pub fn from_client_config(path: String, config: client::ClientConfig) -> Result<Self, ZipSniperError> {
+        /*
+        # Arguments
+        * `path: String` The URL of the archive
+        * `config: ClientConfig` What the HTTP client is built from: proxies, headers
+            and credentials, TLS settings, timeouts
+        */
+        let client = config.build().map_err(ZipSniperError::ClientError)?;
+        let fetcher = fetch::Fetcher::new(client, path.clone(), fetch::RangePlanner::default())
+            .with_read_timeout(config.timeouts.read);
+        Ok(Self {
+            fetcher,
             path,
-            client: Client::new(),
-        }
+            name_encoding: encoding::NameEncoding::default(),
+        })
     }
 
-    pub async fn run(&self, comment_buffer: u64) -> Vec<cd::Cd> {
-        debug!("running zipSniper against remote file: {}", {&self.path});
-        let buffer = self.get_buffer(comment_buffer).await;
-        let eocd = self.parse_out_eocd(buffer.unwrap());
+    pub fn server_certificate_fingerprint(&self) -> Option<String> {
+        /*
+        The SHA-256 fingerprint of the certificate the server presented, None as
+        long as no HTTPS request was answered
+        */
+        self.fetcher.server_certificate().map(client::certificate_fingerprint)
+    }
 
-        let cd_count = eocd.total_number_of_central_directory_records();
-        let cd_offset = eocd.offset_of_start_of_central_directory();
-        let cd_size = eocd.size_of_central_directory();
+    pub fn with_range_planner(mut self, planner: fetch::RangePlanner) -> Self {
+        /*
+        Replaces the planner deciding how byte ranges are merged into requests
+        */
+        self.fetcher = self.fetcher.with_planner(planner);
+        self
+    }
 
-        let cd = self.get_cd(cd_size, cd_offset).await;
-        let cd_list = self.parse_out_cds(cd.unwrap());
+    pub fn with_retry_policy(mut self, retry_policy: fetch::RetryPolicy) -> Self {
+        /*
+        Replaces the policy deciding how requests failing for a transient reason are
+        retried
+        */
+        self.fetcher = self.fetcher.with_retry_policy(retry_policy);
+        self
+    }
 
-        if cd_list.len() as u64 != eocd.number_of_central_directory_records_on_this_disk() {
-            error!("[WARNING] - Missing {} CD Records!", {
-                eocd.number_of_central_directory_records_on_this_disk() - cd_list.len() as u64
-            });
-        }
+    pub fn retries(&self) -> u64 {
+        /*
+        The number of requests sent again after a transient failure so far
+        */
+        self.fetcher.retries()
+    }
 
-        cd_list
+    pub async fn with_volumes(mut self, paths: Vec<String>) -> Result<Self, ZipSniperError> {
+        self.load_volumes(paths).await?;
+        Ok(self)
     }
 
-    async fn get_buffer(&self, comment_buffer: u64) -> Result<Bytes, ZipSniperError> {
+    pub async fn load_volumes(&mut self, paths: Vec<String>) -> Result<(), ZipSniperError> {
         /*
-        Grabs the last <comment_buffer> bytes of the ZIP archive self.path
+        Reads a split archive out of several remote files. <paths> lists the volumes
+        in disk order (.z01, .z02, ..., .zip), the last one holding the EOCD. Unlike
+        with_volumes the sniper outlives a failure, so its retries can be reported.
+
+        # Arguments
+        * `paths: Vec<String>` The URL of every volume
+        */
+        self.fetcher.load_volumes(paths).await?;
+        self.path = self.fetcher.path().to_string();
+        Ok(())
+    }
+
+    pub fn with_name_encoding(mut self, name_encoding: encoding::NameEncoding) -> Self {
+        /*
+        Sets the code page of the names and comments which are not flagged as UTF-8
+        */
+        self.name_encoding = name_encoding;
+        self
+    }
+
+    pub async fn run(&self, comment_buffer: u64) -> Result<Vec<cd::Cd>, ZipSniperError> {
+        debug!("running zipSniper against remote file: {}", {&self.path});
+        let eocd = self.read_eocd(comment_buffer).await?;
+        self.read_cds(&eocd).await
+    }
+
+    pub async fn read_eocd(&self, comment_buffer: u64) -> Result<eocd::Eocd, ZipSniperError> {
+        /*
+        Locates the EOCD at the end of the archive and follows it to the ZIP64 EOCD
+        record when the archive needs one.
 
         The EOCD is the last data structure in a ZIP archive with the start of this structure is identified
             with a signature.
         0x06054b50 with offset of atleast -22 Bytes + N
-        0x06064b50 with offset of atleast -56 Bytes + N
         Where N is the size of the comment.
         We grab a small chunk near the end of the ZIP with a HTTP GET request using
-            the Range header and hope the signature is present in the response.
+            the Range header and widen it until the signature is present in the response.
+
+        # Arguments
+        * `comment_buffer: u64` The size of the first chunk to take from the end of
+            the archive.
+        */
+        let (buffer_offset, buffer, eocd_position, eocd) = self.search_eocd(comment_buffer).await?;
+        self.check_volume_count(&eocd)?;
+        let (cd_end, mut eocd) = self
+            .resolve_zip64_eocd(eocd, eocd_position, buffer_offset, &buffer)
+            .await?;
+
+        eocd.base_offset = self.locate_central_directory(&eocd, cd_end).await?;
+        if eocd.base_offset > 0 {
+            info!(
+                "{} bytes of data are prepended to the archive (self-extracting stub?), shifting every offset by that much",
+                eocd.base_offset
+            );
+        }
+        Ok(eocd)
+    }
+
+    pub async fn read_cds(&self, eocd: &eocd::Eocd) -> Result<Vec<cd::Cd>, ZipSniperError> {
+        /*
+        Pulls the Central Directory named by the EOCD and walks it into its records.
+        */
+        let cd_offset = self.central_directory_offset(eocd)?;
+        let cd_size = eocd.size_of_central_directory();
+
+        /*
+        The central directory (cd) is a small section of data that contains a listing of
+        all the files within the archive. Knowing the offset and the size of the cd, we can snipe
+        it out with the Range header
+        */
+        debug!("Grabbing the CD blob between offsets {} and {}", cd_offset, cd_offset + cd_size);
+        let cd = self.fetcher.fetch_range(fetch::ByteRange::new(cd_offset, cd_size)).await?;
+        self.parse_out_cds(cd, cd_offset, eocd)
+    }
+
+    pub async fn read_local_header(&self, cd: &cd::Cd) -> Result<lfh::Lfh, ZipSniperError> {
+        /*
+        Pulls and parses the local file header of a single entry.
+        */
+        let mut lfhs = self.read_local_headers(&[cd]).await?;
+        Ok(lfhs.remove(0))
+    }
+
+    pub async fn read_local_headers(&self, cds: &[&cd::Cd]) -> Result<Vec<lfh::Lfh>, ZipSniperError> {
+        /*
+        Pulls and parses the local file headers of several entries, in the order of <cds>.
+
+        The length of every header is estimated from its CD record and all of them are
+        pulled through the range planner, so neighbouring headers share a request.
+        Should a header turn out to be longer than estimated, the rest of it is pulled
+        on its own.
 
         # Arguments
-        * `comment_buffer: u64` The size of the chunk to take from the end of the
-            archive.
+        * `cds: &[&Cd]` The CD records of the entries
         */
+        let windows = cds
+            .iter()
+            .map(|cd| {
+                let length = (lfh::Lfh::HEADER_SIZE
+                    + cd.file_name_length() as usize
+                    + cd.extra_field_length() as usize) as u64
+                    + Self::LOCAL_HEADER_SLACK;
+                Ok(fetch::ByteRange::new(self.local_header_offset(cd)?, length))
+            })
+            .collect::<Result<Vec<_>, ZipSniperError>>()?;
+        debug!("Pulling {} local file headers", cds.len());
+        let binaries = self.fetcher.fetch_ranges(&windows).await?;
 
-        debug!("pulling the last {} bytes from the file {}", comment_buffer, &self.path);
-        let response = self
-            .client
-            .get(&self.path)
-            .header("Range", format!("bytes=-{}", comment_buffer))
-            .send()
-            .await
-            .map_err(ZipSniperError::HttpError)?;
+        let mut lfhs = Vec::with_capacity(cds.len());
+        for (window, mut binary) in windows.into_iter().zip(binaries) {
+            if let Some(length) = lfh::Lfh::required_length(&binary).filter(|length| *length > binary.len()) {
+                binary = self.fetcher.fetch_range(fetch::ByteRange::new(window.start, length as u64)).await?;
+            }
 
-        let status = response.status();
-        if !status.is_success() {
-            return Err(ZipSniperError::InvalidStatusCode(status));
+            let lfh = lfh::Lfh::parse(window.start, binary).map_err(|reason| {
+                ZipSniperError::MalformedLocalHeader { offset: window.start, reason }
+            })?;
+            lfhs.push(lfh);
         }
-        
-        let data = response.bytes().await.unwrap();
-        debug!("Last {} bytes pulled from {}", data.len(), &self.path);
-        Ok(data)
+
+        Ok(lfhs)
     }
 
-    fn parse_out_cds(&self, buffer: Bytes) -> Vec<cd::Cd> {
+    pub fn estimate_archive_map(&self, eocd: &eocd::Eocd, cds: &[cd::Cd]) -> Result<map::ArchiveMap, ZipSniperError> {
         /*
-        Giving a string of bytes like below where A is a data signature
-        A B C D E A B C D E F A B C A,
+        Builds the byte range map of the archive from the CD records alone, without
+        pulling anything. The entries of a split archive are placed on the volume
+        they start on.
+        */
+        let spans = cds
+            .iter()
+            .enumerate()
+            .map(|(index, cd)| Ok(map::EntrySpan::from_cd(index, self.local_header_offset(cd)?, cd)))
+            .collect::<Result<_, ZipSniperError>>()?;
+
+        Ok(map::ArchiveMap::from_spans(spans, self.central_directory_offset(eocd)?))
+    }
 
-        This function will split the string of bytes into sections where each section
-        starts with A.
+    pub async fn read_archive_map(&self, eocd: &eocd::Eocd, cds: &[cd::Cd]) -> Result<map::ArchiveMap, ZipSniperError> {
+        /*
+        Builds the byte range map of the archive from the local file headers, so the
+        start of the compressed data of every entry is exact. The data descriptors of
+        entries with general purpose bit 3 set are pulled as well, as their layout
+        decides how far the entry reaches.
         */
+        let lfhs = self.read_local_headers(&cds.iter().collect::<Vec<_>>()).await?;
+        let described: Vec<(usize, &cd::Cd, &lfh::Lfh)> = cds
+            .iter()
+            .zip(lfhs.iter())
+            .enumerate()
+            .filter(|(_, (_, lfh))| lfh.flags().contains(flags::GeneralPurposeFlags::DATA_DESCRIPTOR))
+            .map(|(index, (cd, lfh))| (index, cd, lfh))
+            .collect();
+        let ranges: Vec<fetch::ByteRange> = described
+            .iter()
+            .map(|(_, cd, lfh)| {
+                fetch::ByteRange::new(
+                    lfh.data_offset() + cd.compressed_size(),
+                    descriptor::DataDescriptor::MAX_SIZE as u64,
+                )
+            })
+            .collect();
+        let mut descriptor_lengths = vec![0; cds.len()];
+        for ((index, cd, lfh), binary) in described.into_iter().zip(self.fetcher.fetch_ranges(&ranges).await?) {
+            if let Some(descriptor) = ZipSniper::parse_descriptor(cd, lfh, binary) {
+                descriptor_lengths[index] = descriptor.length() as u64;
+            }
+        }
 
-        let signature: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+        let spans = cds
+            .iter()
+            .zip(lfhs.iter())
+            .enumerate()
+            .map(|(index, (cd, lfh))| {
+                map::EntrySpan::new(index, lfh.offset(), lfh.header_length() as u64, cd)
+                    .with_descriptor_length(descriptor_lengths[index])
+            })
+            .collect();
 
-        let mut data_structures = Vec::new();
-        let mut start_indices = Vec::new();
-        //Find the start indices of the signature in the buffer
-        debug!("CD Blob is {} bytes in size", buffer.len());
-        for (index, win) in buffer.windows(signature.len()).enumerate() {
-            if win == signature {
-                start_indices.push(index);
+        Ok(map::ArchiveMap::from_spans(spans, self.central_directory_offset(eocd)?))
+    }
+
+    pub fn find_entry<'a>(cds: &'a [cd::Cd], name: &str) -> Option<&'a cd::Cd> {
+        /*
+        Returns the CD record of the entry called <name>, as listed
+        */
+        cds.iter()
+            .find(|cd| cd.file_name_lossy() == name)
+    }
+
+    pub async fn extract_entry(&self, cd: &cd::Cd) -> Result<Bytes, ZipSniperError> {
+        /*
+        Pulls a single entry out of the archive without downloading the rest of it.
+
+        Only the local file header and the compressed data of the entry are pulled,
+        the compressed data starts right after the local file header and is as long as
+        the compressed size recorded in the CD.
+
+        # Arguments
+        * `cd: &Cd` The CD record of the entry to extract
+        */
+        let entry = self.read_entry_data(&[cd]).await?.remove(0);
+        let (discrepancies, content) = ZipSniper::check_entry(cd, entry);
+        for discrepancy in discrepancies {
+            warn!("{}: {}", ZipSniper::entry_name(cd), discrepancy);
+        }
+        content
+    }
+
+    pub async fn extract_entry_to(&self, cd: &cd::Cd, path: &Path) -> Result<u64, ZipSniperError> {
+        /*
+        Pulls a single entry out of the archive and writes it to <path>.
+        Returns the number of bytes written.
+        */
+        let data = self.extract_entry(cd).await?;
+        ZipSniper::write_entry(cd, path, &data).await
+    }
+
+    pub async fn extract_entries(
+        &self,
+        cds: &[cd::Cd],
+        filter: &filter::EntryFilter,
+        output_directory: &Path,
+    ) -> extract::ExtractSummary {
+        /*
+        Pulls every entry selected by <filter> out of the archive and writes it below
+        <output_directory>, keeping the directory structure of the archive.
+
+        Entry names are sanitised first, names that would escape the output directory
+        are skipped. The selected entries are pulled in batches through the range
+        planner, so neighbouring entries share requests. A failure to extract one entry
+        does not stop the others, every entry that is not written ends up in the
+        skipped list of the summary with the reason why.
+
+        # Arguments
+        * `cds: &[Cd]` The CD records of the archive
+        * `filter: &EntryFilter` Selects the entries to extract by name
+        * `output_directory: &Path` The directory to write the entries below
+        */
+        let mut summary = extract::ExtractSummary::default();
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+        for cd in cds {
+            let name = ZipSniper::entry_name(cd);
+            match filter.check(&name) {
+                filter::FilterResult::Selected => (),
+                filter::FilterResult::NotIncluded => {
+                    summary.skipped.push((name, extract::SkipReason::NotIncluded));
+                    continue;
+                }
+                filter::FilterResult::Excluded => {
+                    summary.skipped.push((name, extract::SkipReason::Excluded));
+                    continue;
+                }
+            }
+
+            let (relative_path, rewritten) = match extract::sanitise_path(&name) {
+                Ok(sanitised) => sanitised,
+                Err(reason) => {
+                    warn!("Skipping {}: unsafe path: {}", name, reason);
+                    summary.skipped.push((name, extract::SkipReason::UnsafePath(reason)));
+                    continue;
+                }
+            };
+            let path = output_directory.join(&relative_path);
+
+            if cd.file_attributes().file_type() == attributes::FileType::Directory {
+                match tokio::fs::create_dir_all(&path).await {
+                    Ok(()) => summary.extracted.push(extract::ExtractedEntry { name, path, size: 0, rewritten }),
+                    Err(err) => {
+                        let reason = extract::ExtractError::Io(err);
+                        let err = ZipSniperError::ExtractionFailed { name: name.clone(), reason };
+                        summary.skipped.push((name, extract::SkipReason::Failed(err)));
+                    }
+                }
+                continue;
+            }
+            if let Err(err) = ZipSniper::ensure_extractable(cd) {
+                warn!("Skipping {}: {}", name, err);
+                summary.skipped.push((name, extract::SkipReason::Failed(err)));
+                continue;
             }
+
+            batch_size += cd.compressed_size();
+            batch.push((cd, extract::ExtractedEntry { name, path, size: 0, rewritten }));
+            if batch_size >= Self::EXTRACT_BATCH_SIZE {
+                self.extract_batch(std::mem::take(&mut batch), &mut summary).await;
+                batch_size = 0;
+            }
+        }
+        if !batch.is_empty() {
+            self.extract_batch(batch, &mut summary).await;
         }
 
-        //Extract the cd for the buffer using the calculated indices
-        for i in 0..start_indices.len() {
-            let start = start_indices[i];
-            let end = if i < start_indices.len() - 1 {
-                start_indices[i + 1]
-            } else {
-                buffer.len()
+        summary
+    }
+
+    async fn extract_batch(
+        &self,
+        batch: Vec<(&cd::Cd, extract::ExtractedEntry)>,
+        summary: &mut extract::ExtractSummary,
+    ) {
+        /*
+        Pulls a batch of entries together and writes every entry out.
+        */
+        let cds: Vec<&cd::Cd> = batch.iter().map(|(cd, _)| *cd).collect();
+        let contents = self.read_batch_contents(&cds).await;
+
+        for ((cd, mut entry), (discrepancies, content)) in batch.into_iter().zip(contents) {
+            for discrepancy in discrepancies {
+                warn!("{}: {}", entry.name, discrepancy);
+            }
+            let result = match content {
+                Ok(content) => ZipSniper::write_entry(cd, &entry.path, &content).await,
+                Err(err) => Err(err),
             };
 
-            let data_structure = buffer.slice(start..end);
-            let cd = cd::Cd::from(data_structure);
-            data_structures.push(cd);
+            match result {
+                Ok(size) => {
+                    debug!("Extracted {} to {}", entry.name, entry.path.display());
+                    entry.size = size;
+                    summary.extracted.push(entry);
+                }
+                Err(err) => {
+                    warn!("Skipping {}: {}", entry.name, err);
+                    summary.skipped.push((entry.name, extract::SkipReason::Failed(err)));
+                }
+            }
+        }
+    }
+
+    pub async fn verify_entries(&self, cds: &[cd::Cd], filter: &filter::EntryFilter) -> extract::VerifySummary {
+        /*
+        Pulls every entry selected by <filter> and checks the CRC-32 of its content
+        against the values recorded in the archive, without writing anything out.
+        The local file header and data descriptor of every entry are cross-checked
+        against its CD record along the way.
+
+        # Arguments
+        * `cds: &[Cd]` The CD records of the archive
+        * `filter: &EntryFilter` Selects the entries to verify by name
+        */
+        let mut summary = extract::VerifySummary::default();
+        let mut batch = Vec::new();
+        let mut batch_size = 0;
+        for cd in cds {
+            let name = ZipSniper::entry_name(cd);
+            match filter.check(&name) {
+                filter::FilterResult::Selected => (),
+                filter::FilterResult::NotIncluded => {
+                    summary.skipped.push((name, extract::SkipReason::NotIncluded));
+                    continue;
+                }
+                filter::FilterResult::Excluded => {
+                    summary.skipped.push((name, extract::SkipReason::Excluded));
+                    continue;
+                }
+            }
+            if let Err(err) = ZipSniper::ensure_extractable(cd) {
+                summary.entries.push((name, Err(err)));
+                continue;
+            }
+
+            batch_size += cd.compressed_size();
+            batch.push(cd);
+            if batch_size >= Self::EXTRACT_BATCH_SIZE {
+                self.verify_batch(std::mem::take(&mut batch), &mut summary).await;
+                batch_size = 0;
+            }
+        }
+        if !batch.is_empty() {
+            self.verify_batch(batch, &mut summary).await;
+        }
+
+        summary
+    }
+
+    async fn verify_batch(&self, batch: Vec<&cd::Cd>, summary: &mut extract::VerifySummary) {
+        let contents = self.read_batch_contents(&batch).await;
+        for (cd, (discrepancies, content)) in batch.into_iter().zip(contents) {
+            let name = ZipSniper::entry_name(cd);
+            if !discrepancies.is_empty() {
+                summary.discrepancies.push((name.clone(), discrepancies));
+            }
+            let result = content.map(|_| cd.crc_32_of_uncompressed_data());
+            summary.entries.push((name, result));
+        }
+    }
+
+    async fn read_batch_contents(
+        &self,
+        cds: &[&cd::Cd],
+    ) -> Vec<(Vec<extract::Discrepancy>, Result<Bytes, ZipSniperError>)> {
+        /*
+        Pulls the compressed data of a batch of entries together and turns it back into
+        the content of every entry. Should pulling the batch fail, the entries are pulled
+        one by one so every entry ends up with its own result.
+        */
+        match self.read_entry_data(cds).await {
+            Ok(batch_data) => cds
+                .iter()
+                .zip(batch_data)
+                .map(|(cd, entry)| ZipSniper::check_entry(cd, entry))
+                .collect(),
+            Err(err) => {
+                debug!("Pulling a batch of {} entries failed ({}), pulling them one by one", cds.len(), err);
+                let mut contents = Vec::with_capacity(cds.len());
+                for cd in cds {
+                    contents.push(match self.read_entry_data(&[cd]).await {
+                        Ok(mut entry) => ZipSniper::check_entry(cd, entry.remove(0)),
+                        Err(err) => (Vec::new(), Err(err)),
+                    });
+                }
+                contents
+            }
+        }
+    }
+
+    async fn read_entry_data(&self, cds: &[&cd::Cd]) -> Result<Vec<EntryData>, ZipSniperError> {
+        /*
+        Pulls the local file header and the compressed data of several entries, in the
+        order of <cds>. The local file headers are pulled first, as the data starts
+        right after them.
+
+        Entries with general purpose bit 3 set in their local file header are followed
+        by a data descriptor. The compressed size from the CD tells where it starts, so
+        it is pulled along with the data.
+        */
+        for cd in cds {
+            ZipSniper::ensure_extractable(cd)?;
+        }
+
+        let lfhs = self.read_local_headers(cds).await?;
+        let ranges: Vec<fetch::ByteRange> = cds
+            .iter()
+            .zip(lfhs.iter())
+            .map(|(cd, lfh)| {
+                debug!(
+                    "Extracting {} ({} bytes at offset {})",
+                    ZipSniper::entry_name(cd), cd.compressed_size(), lfh.data_offset()
+                );
+                let mut length = cd.compressed_size();
+                if lfh.flags().contains(flags::GeneralPurposeFlags::DATA_DESCRIPTOR) {
+                    length += descriptor::DataDescriptor::MAX_SIZE as u64;
+                }
+                fetch::ByteRange::new(lfh.data_offset(), length)
+            })
+            .collect();
+
+        let data = self.fetcher.fetch_ranges(&ranges).await?;
+        Ok(cds
+            .iter()
+            .zip(lfhs)
+            .zip(data)
+            .map(|((cd, lfh), data)| {
+                let compressed_size = cd.compressed_size() as usize;
+                let descriptor = match lfh.flags().contains(flags::GeneralPurposeFlags::DATA_DESCRIPTOR) {
+                    true => ZipSniper::parse_descriptor(cd, &lfh, data.slice(compressed_size..)),
+                    false => None,
+                };
+                if let Some(descriptor) = descriptor.as_ref() {
+                    trace!(
+                        "Data descriptor of {} at offset {}: {} bytes, signed: {}",
+                        ZipSniper::entry_name(cd),
+                        lfh.data_offset() + compressed_size as u64,
+                        descriptor.length(),
+                        descriptor.signed
+                    );
+                }
+                EntryData {
+                    lfh,
+                    descriptor,
+                    data: data.slice(..compressed_size),
+                }
+            })
+            .collect())
+    }
+
+    fn parse_descriptor(cd: &cd::Cd, lfh: &lfh::Lfh, binary: Bytes) -> Option<descriptor::DataDescriptor> {
+        /*
+        Reads the data descriptor at the start of <binary>, the bytes right after the
+        compressed data. Its sizes are taken to be 64-bit when the local file header
+        carries ZIP64 extended information.
+        */
+        let zip64 = lfh
+            .extra_fields()
+            .any(|field| matches!(field, extra::ExtraField::Zip64ExtendedInformation(_)));
+        descriptor::DataDescriptor::parse(binary, zip64, cd)
+    }
+
+    fn ensure_extractable(cd: &cd::Cd) -> Result<(), ZipSniperError> {
+        if cd.flags().contains(flags::GeneralPurposeFlags::ENCRYPTED) {
+            return Err(ZipSniperError::ExtractionFailed {
+                name: ZipSniper::entry_name(cd),
+                reason: extract::ExtractError::Encrypted,
+            });
+        }
+        Ok(())
+    }
+
+    fn check_entry(
+        cd: &cd::Cd,
+        entry: EntryData,
+    ) -> (Vec<extract::Discrepancy>, Result<Bytes, ZipSniperError>) {
+        /*
+        Cross-checks the local structures of an entry against its CD record, then
+        turns its compressed data back into its content.
+        */
+        let discrepancies = extract::cross_check(cd, &entry.lfh, entry.descriptor.as_ref());
+        (discrepancies, ZipSniper::decode_entry(cd, entry))
+    }
+
+    fn decode_entry(cd: &cd::Cd, entry: EntryData) -> Result<Bytes, ZipSniperError> {
+        /*
+        Turns the compressed data of an entry back into its content and checks the
+        CRC-32 of that content against the CD record, the local file header and the
+        data descriptor. The local file header only carries a CRC-32 when general
+        purpose bit 3 is clear, the data descriptor only when it is set.
+        */
+        let failed = |reason| ZipSniperError::ExtractionFailed {
+            name: ZipSniper::entry_name(cd),
+            reason,
+        };
+
+        let content = extract::decompress(cd.compression(), entry.data, cd.uncompressed_size()).map_err(failed)?;
+        let actual = crc32fast::hash(&content);
+
+        let mut expected = vec![(extract::RecordSource::CentralDirectory, cd.crc_32_of_uncompressed_data())];
+        if !entry.lfh.flags().contains(flags::GeneralPurposeFlags::DATA_DESCRIPTOR) {
+            expected.push((extract::RecordSource::LocalHeader, entry.lfh.crc_32_of_uncompressed_data()));
+        }
+        if let Some(descriptor) = entry.descriptor.as_ref() {
+            expected.push((extract::RecordSource::DataDescriptor, descriptor.crc_32_of_uncompressed_data()));
+        }
+        for (source, expected) in expected {
+            if expected != actual {
+                return Err(failed(extract::ExtractError::CrcMismatch { source, expected, actual }));
+            }
+        }
+
+        Ok(content)
+    }
+
+    async fn write_entry(cd: &cd::Cd, path: &Path, data: &Bytes) -> Result<u64, ZipSniperError> {
+        /*
+        Writes the content of an entry to <path>, creating the directories leading up to it.
+        */
+        let io_failed = |err| ZipSniperError::ExtractionFailed {
+            name: ZipSniper::entry_name(cd),
+            reason: extract::ExtractError::Io(err),
+        };
+
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await.map_err(io_failed)?;
+        }
+        tokio::fs::write(path, data).await.map_err(io_failed)?;
+
+        Ok(data.len() as u64)
+    }
+
+    fn entry_name(cd: &cd::Cd) -> String {
+        cd.file_name_lossy()
+    }
+
+    fn central_directory_offset(&self, eocd: &eocd::Eocd) -> Result<u64, ZipSniperError> {
+        /*
+        The position of the CD within the archive, which for a split archive is the
+        offset on the disk the CD starts on plus the size of the volumes before it
+        */
+        let position = self
+            .fetcher
+            .position(eocd.disk_where_cd_starts(), eocd.offset_of_start_of_central_directory())?;
+        Ok(eocd.base_offset + position)
+    }
+
+    fn local_header_offset(&self, cd: &cd::Cd) -> Result<u64, ZipSniperError> {
+        /*
+        The position of the local file header of an entry within the archive, see
+        central_directory_offset
+        */
+        let position = self
+            .fetcher
+            .position(cd.disk_where_file_starts(), cd.relative_offset_of_local_file_header())?;
+        Ok(cd.base_offset + position)
+    }
+
+    async fn locate_central_directory(&self, eocd: &eocd::Eocd, cd_end: u64) -> Result<u64, ZipSniperError> {
+        /*
+        Returns the number of bytes prepended to the archive.
+
+        The CD ends where the EOCD (or the ZIP64 EOCD record) starts, so a CD which does
+        not end there is either followed by data the EOCD does not account for, or
+        the whole archive was shifted by data put in front of it: a self-extracting
+        stub, a shell script installer or another file. In the first case the CD
+        signature is found where the EOCD says, in the second the real CD is searched
+        backwards from the EOCD and the distance to the recorded offset is returned.
+
+        # Arguments
+        * `eocd: &Eocd` The EOCD naming the CD
+        * `cd_end: u64` The position of the record following the CD
+        */
+        const SIGNATURE: &[u8] = &[0x50, 0x4b, 0x01, 0x02];
+
+        let expected = self.central_directory_offset(eocd)?;
+        let cd_size = eocd.size_of_central_directory();
+        if cd_size == 0 || expected + cd_size == cd_end {
+            return Ok(0);
+        }
+
+        if expected + SIGNATURE.len() as u64 <= cd_end {
+            let binary = self
+                .fetcher
+                .fetch_range(fetch::ByteRange::new(expected, SIGNATURE.len() as u64))
+                .await?;
+            if binary == SIGNATURE {
+                debug!("{} bytes lie between the CD and the EOCD", cd_end - expected - cd_size);
+                return Ok(0);
+            }
+        }
+
+        let not_found = ZipSniperError::CentralDirectoryNotFound(expected);
+        let search_end = cd_end.checked_sub(cd_size).ok_or(not_found)? + SIGNATURE.len() as u64;
+        let search_start = search_end.saturating_sub(Self::CENTRAL_DIRECTORY_SEARCH_WINDOW);
+        debug!(
+            "No CD at offset {}, searching backwards between offsets {} and {}",
+            expected, search_start, search_end
+        );
+        let window = self
+            .fetcher
+            .fetch_range(fetch::ByteRange::new(search_start, search_end - search_start))
+            .await?;
+
+        match ZipSniper::get_signature_position_reverse(SIGNATURE, &window) {
+            Some(position) if search_start + position as u64 > expected => {
+                Ok(search_start + position as u64 - expected)
+            }
+            _ => Err(ZipSniperError::CentralDirectoryNotFound(expected)),
+        }
+    }
+
+    fn check_volume_count(&self, eocd: &eocd::Eocd) -> Result<(), ZipSniperError> {
+        /*
+        The EOCD sits on the last disk, so its disk number tells how many volumes the
+        archive was split into. Archives needing ZIP64 may leave it at 0xFFFF, the
+        ZIP64 record then holds the real number.
+        */
+        let disks = eocd.number_of_this_disk();
+        if disks == 0xFFFF {
+            return Ok(());
+        }
+        let expected = disks as usize + 1;
+        let found = self.fetcher.volumes().len();
+        match (expected == found, found) {
+            (true, _) => Ok(()),
+            (false, 1) => {
+                warn!("The archive spans {} disks but a single volume was given, offsets are taken as is", expected);
+                Ok(())
+            }
+            (false, _) => Err(ZipSniperError::VolumeCountMismatch { expected, found }),
         }
-        data_structures
     }
 
-    fn parse_out_eocd(&self, buffer: Bytes) -> eocd::Eocd {
+    fn parse_out_cds(&self, buffer: Bytes, cd_offset: u64, eocd: &eocd::Eocd) -> Result<Vec<cd::Cd>, ZipSniperError> {
         /*
-        Looks for the start of the EOCD signature and returns the EOCD.
+        Walks the CD blob one entry at a time.
+
+        Every entry starts with a fixed 46 byte header which holds the lengths of the
+        variable fields that follow it, so the next entry starts at
+        46 + file_name_length + extra_field_length + file_comment_length.
+        The walk has to consume exactly the size of the central directory recorded in
+        the EOCD and produce the number of records it announces.
 
-        The signatures to look for are:
+        # Arguments
+        * `buffer: Bytes` The CD blob
+        * `cd_offset: u64` The position of the CD blob within the archive
+        * `eocd: &Eocd` The EOCD the CD blob was located with
+        */
+        let cd_size = eocd.size_of_central_directory();
+        debug!("CD Blob is {} bytes in size", buffer.len());
+        if buffer.len() as u64 != cd_size {
+            return Err(ZipSniperError::CentralDirectorySizeMismatch {
+                expected: cd_size,
+                found: buffer.len() as u64,
+            });
+        }
+
+        let mut cd_list = Vec::new();
+        let mut offset = 0;
+        while offset < buffer.len() {
+            let mut cd = cd::Cd::try_from(buffer.slice(offset..)).map_err(|reason| {
+                ZipSniperError::MalformedCentralDirectory {
+                    offset: cd_offset + offset as u64,
+                    reason,
+                }
+            })?;
+            trace!("CD record at offset {} is {} bytes long", offset, cd.record_length());
+            offset += cd.record_length();
+            cd.name_encoding = self.name_encoding;
+            cd.base_offset = eocd.base_offset;
+            cd_list.push(cd);
+        }
+
+        /*
+        Archives without ZIP64 records can only count up to 0xFFFF entries, some writers
+        let the count wrap around instead of switching to ZIP64.
+        */
+        let expected = eocd.total_number_of_central_directory_records();
+        let found = cd_list.len() as u64;
+        let wrapped = eocd.word_size == eocd::WordSize::Bit32 && found & 0xFFFF == expected;
+        if found != expected && !wrapped {
+            return Err(ZipSniperError::CentralDirectoryRecordCountMismatch { expected, found });
+        } else if found != expected {
+            warn!("EOCD record count {} wrapped around, walked {} CD records", expected, found);
+        }
+
+        Ok(cd_list)
+    }
+
+    async fn search_eocd(&self, initial_window: u64) -> Result<(u64, Bytes, usize, eocd::Eocd), ZipSniperError> {
+        /*
+        Pulls the last <initial_window> bytes of the archive and looks for the EOCD in
+        them. While it is not found, or only found with bytes trailing its comment, the
+        window grows step by step and only the bytes in front of what is already held
+        are pulled, until the window covers the largest possible comment or the start
+        of the archive.
+
+        Returns the position of the buffer within the archive, the buffer, and the
+        position of the EOCD within the buffer along with the EOCD.
+
+        # Arguments
+        * `initial_window: u64` The size of the first chunk to take from the end of
+            the archive
+        */
+        let mut window = initial_window.max(eocd::Eocd::EOCD_32_SIZE as u64);
+        debug!("pulling the last {} bytes from the file {}", window, &self.path);
+        let (mut buffer_offset, mut buffer) = self.fetcher.fetch_tail(window).await?;
+        let mut round_trips = 1;
+
+        loop {
+            debug!("Last {} bytes pulled from {} (offset {})", buffer.len(), &self.path, buffer_offset);
+            let can_widen = buffer_offset > 0 && window < Self::EOCD_SEARCH_LIMIT;
+            match self.parse_out_eocd(&buffer) {
+                Ok((eocd_position, eocd, exact)) if exact || !can_widen => {
+                    if !exact {
+                        let comment_end =
+                            eocd_position + eocd::Eocd::EOCD_32_SIZE + eocd.zip_file_comment_length() as usize;
+                        warn!(
+                            "No EOCD comment reaches the end of the archive, using the last EOCD which fits, {} bytes follow its comment",
+                            buffer.len() - comment_end
+                        );
+                    }
+                    let message = format!(
+                        "EOCD found {} bytes from the end of the archive, after pulling {} bytes in {} requests",
+                        buffer.len() - eocd_position,
+                        buffer.len(),
+                        round_trips
+                    );
+                    match round_trips {
+                        1 => debug!("{}", message),
+                        _ => info!("{}", message),
+                    }
+                    return Ok((buffer_offset, buffer, eocd_position, eocd));
+                }
+                Ok(_) => (),
+                Err(err) if !can_widen => return Err(err),
+                Err(_) => (),
+            }
+
+            window = (window * Self::EOCD_SEARCH_GROWTH).min(Self::EOCD_SEARCH_LIMIT);
+            let extra = window.saturating_sub(buffer.len() as u64).min(buffer_offset);
+            debug!("No EOCD in the last {} bytes, widening the search to {} bytes", buffer.len(), window);
+            let head = self
+                .fetcher
+                .fetch_range(fetch::ByteRange::new(buffer_offset - extra, extra))
+                .await?;
+            round_trips += 1;
+
+            let mut widened = BytesMut::with_capacity(head.len() + buffer.len());
+            widened.extend_from_slice(&head);
+            widened.extend_from_slice(&buffer);
+            buffer_offset -= extra;
+            buffer = widened.freeze();
+        }
+    }
+
+    fn parse_out_eocd(&self, buffer: &Bytes) -> Result<(usize, eocd::Eocd, bool), ZipSniperError> {
+        /*
+        Looks for the start of the 32-bit EOCD signature and returns its position
+        within the buffer along with the EOCD, and whether its comment ends exactly
+        at the end of the buffer.
+
+        The signature to look for is:
         0x06054b50 with offset of atleast -22 Bytes + N
-        0x06064b50 with offset of atleast -56 Bytes + N
         Where N is the size of the comment.
 
+        The comment of the real EOCD runs up to the end of the archive. A signature
+        planted inside that comment comes after the real one, so the search prefers
+        the candidate whose comment ends exactly at the end of the buffer over any
+        candidate closer to the end. Should no comment end there, as when data was
+        appended to the archive, the candidate closest to the end whose comment fits
+        within the buffer is returned.
+
         # Arguments
-        * `buffer: Bytes` The chunk of data to look in for the eocd data structure
+        * `buffer: &Bytes` The chunk of data to look in for the eocd data structure
         */
         const SIGNATURE_32: &[u8] = &[0x50, 0x4b, 0x05, 0x06];
-        const SIGNATURE_64: &[u8] = &[0x50, 0x4b, 0x06, 0x06];
-
-        debug!("Checking for the signatures {:?} or {:?} within the {} bytes", SIGNATURE_32, SIGNATURE_64, buffer.len());
-        let pos = match ZipSniper::get_signature_position_reverse(SIGNATURE_32, &buffer) {
-            Some(n) => n,
-            None => match ZipSniper::get_signature_position_reverse(SIGNATURE_64, &buffer) {
-                Some(m) => m,
-                None => panic!("{}", ZipSniperError::EocdSignatureNotFound),
-            },
+
+        debug!("Checking for the signature {:?} within the {} bytes", SIGNATURE_32, buffer.len());
+        let mut search_end = buffer.len();
+        let mut fitting = None;
+        while let Some(pos) = ZipSniper::get_signature_position_reverse(SIGNATURE_32, &buffer.slice(..search_end)) {
+            if let Ok(eocd) = eocd::Eocd::try_from(buffer.slice(pos..)) {
+                let comment_end = pos + eocd::Eocd::EOCD_32_SIZE + eocd.zip_file_comment_length() as usize;
+                if comment_end == buffer.len() {
+                    debug!("Signature {:?} found at offset {}", buffer.slice(pos..pos+4), pos);
+                    return Ok((pos, eocd, true));
+                }
+                if comment_end < buffer.len() && fitting.is_none() {
+                    debug!(
+                        "Signature {:?} found at offset {}, {} bytes follow its comment",
+                        buffer.slice(pos..pos+4), pos, buffer.len() - comment_end
+                    );
+                    fitting = Some((pos, eocd));
+                }
+            }
+            search_end = pos + SIGNATURE_32.len() - 1;
+        }
+
+        match fitting {
+            Some((pos, eocd)) => Ok((pos, eocd, false)),
+            None => Err(ZipSniperError::EocdSignatureNotFound { searched: buffer.len() as u64 }),
+        }
+    }
+
+    async fn resolve_zip64_eocd(
+        &self,
+        eocd: eocd::Eocd,
+        eocd_position: usize,
+        buffer_offset: u64,
+        buffer: &Bytes,
+    ) -> Result<(u64, eocd::Eocd), ZipSniperError> {
+        /*
+        Follows the ZIP64 chain from the 32-bit EOCD to the ZIP64 EOCD record and
+        returns it along with its position, the position of the 32-bit EOCD when it
+        is returned untouched.
+
+        The ZIP64 EOCD locator sits directly in front of the 32-bit EOCD and names the
+        absolute offset of the ZIP64 EOCD record. Either structure may fall outside
+        of the buffer we already hold, in which case it is fetched with a ranged GET.
+        If the 32-bit EOCD carries no saturated fields and no locator is present,
+        the 32-bit EOCD is returned untouched.
+
+        # Arguments
+        * `eocd: Eocd` The 32-bit EOCD found by parse_out_eocd
+        * `eocd_position: usize` The position of the 32-bit EOCD within the buffer
+        * `buffer_offset: u64` The absolute offset of the buffer within the remote file
+        * `buffer: &Bytes` The chunk of data pulled from the end of the archive
+        */
+        let locator_size = eocd::Eocd64Locator::SIZE;
+        let eocd_offset = buffer_offset + eocd_position as u64;
+        if eocd_offset < locator_size as u64 {
+            return match eocd.has_zip64_sentinel() {
+                true => Err(ZipSniperError::Zip64LocatorNotFound),
+                false => Ok((eocd_offset, eocd)),
+            };
+        }
+
+        let locator_binary = if eocd_position >= locator_size {
+            buffer.slice(eocd_position - locator_size..eocd_position)
+        } else if !eocd.has_zip64_sentinel() {
+            return Ok((eocd_offset, eocd));
+        } else {
+            debug!("ZIP64 EOCD locator lies outside of the buffer, fetching it");
+            self.fetcher
+                .fetch_range(fetch::ByteRange::new(eocd_offset - locator_size as u64, locator_size as u64))
+                .await?
         };
 
-        debug!("Signature {:?} found at offset {}", buffer.slice(pos..pos+4), pos);
-        eocd::Eocd::from(buffer.slice(pos..))
+        let locator = match eocd::Eocd64Locator::try_from(locator_binary) {
+            Ok(locator) => locator,
+            Err(_) if eocd.has_zip64_sentinel() => return Err(ZipSniperError::Zip64LocatorNotFound),
+            Err(_) => return Ok((eocd_offset, eocd)),
+        };
+
+        let zip64_offset = self.fetcher.position(locator.disk_with_zip64_eocd(), locator.offset_of_zip64_eocd())?;
+        debug!("ZIP64 EOCD locator points to offset {}", zip64_offset);
+
+        /*
+        Data prepended to the archive shifts the ZIP64 EOCD record away from the offset
+        the locator names, it usually sits right in front of the locator though.
+        */
+        let zip64_size = eocd::Eocd::EOCD_64_SIZE as u64;
+        let mut candidates = vec![zip64_offset];
+        if let Some(adjacent) = (eocd_offset - locator_size as u64).checked_sub(zip64_size) {
+            if adjacent != zip64_offset {
+                candidates.push(adjacent);
+            }
+        }
+
+        for candidate in candidates {
+            let zip64_binary = self
+                .slice_or_fetch(fetch::ByteRange::new(candidate, zip64_size), buffer_offset, buffer)
+                .await?;
+            if let Ok(zip64_eocd) = eocd::Eocd::try_from(zip64_binary) {
+                if zip64_eocd.word_size == eocd::WordSize::Bit64 {
+                    debug!("ZIP64 EOCD record found at offset {}", candidate);
+                    return Ok((candidate, zip64_eocd.with_comment(eocd.comment_raw())));
+                }
+            }
+        }
+        Err(ZipSniperError::Zip64EocdNotFound(zip64_offset))
+    }
+
+    async fn slice_or_fetch(
+        &self,
+        range: fetch::ByteRange,
+        buffer_offset: u64,
+        buffer: &Bytes,
+    ) -> Result<Bytes, ZipSniperError> {
+        /*
+        Takes <range> out of the buffer pulled from the end of the archive, or fetches
+        it when it lies outside of the buffer
+        */
+        let buffer_end = buffer_offset + buffer.len() as u64;
+        if range.start >= buffer_offset && range.end <= buffer_end {
+            let start = (range.start - buffer_offset) as usize;
+            return Ok(buffer.slice(start..start + range.len() as usize));
+        }
+        debug!("Bytes {} to {} lie outside of the buffer, fetching them", range.start, range.end);
+        self.fetcher.fetch_range(range).await
     }
 
     fn get_signature_position_reverse(signature: &[u8], data: &Bytes) -> Option<usize> {
         /*
-        Returns index position of the supplied signature within the supplied Bytes object.
+        Returns the index position of the last occurrence of the supplied signature
+        within the supplied Bytes object.
 
         Given a sequence of bytes, this function will return the index position
         of the sub-sequence of bytes. Useful to look for a signature signifying the start
@@ -158,60 +1090,377 @@ impl ZipSniper {
         * `data: Bytes` the sequence of bytes to look in for the subset of bytes
         */
         data.windows(signature.len())
-            .position(|window| window == signature)
-    }
-
-    async fn get_cd(&self, cd_size: u64, cd_offset: u64) -> Result<Bytes, ZipSniperError> {
-        /*
-        Grabs the Central Directory of the ZIP Archive hosted at self.path
-
-        The central directory (cd) is a small section of data that contains a listing of
-        all the files within the archive. Knowing the offset and the size of the cd, we can snipe
-        it out with the Range header
-        */
-
-        debug!("Grabbing the CD blob between offsets {} and {}", cd_offset, cd_offset + cd_size);
-        let response = self
-            .client
-            .get(&self.path)
-            .header(
-                "Range",
-                format!("bytes={}-{}", cd_offset, cd_offset + cd_size),
-            )
-            .send()
-            .await
-            .map_err(ZipSniperError::HttpError)?;
-
-        let status = response.status();
-        if !status.is_success() {
-            return Err(ZipSniperError::InvalidStatusCode(status));
-        }
-
-        let data = response.bytes().await.unwrap();
-        debug!("Last {} bytes pulled from {}", data.len(), &self.path);
-        Ok(data)
+            .rposition(|window| window == signature)
     }
 }
 
 #[derive(Debug)]
-enum ZipSniperError {
+pub enum ZipSniperError {
+    ClientError(client::ClientError),
     HttpError(reqwest::Error),
     InvalidStatusCode(StatusCode),
-    EocdSignatureNotFound,
+    EocdSignatureNotFound { searched: u64 },
+    Zip64LocatorNotFound,
+    Zip64EocdNotFound(u64),
+    MalformedCentralDirectory { offset: u64, reason: cd::CdError },
+    CentralDirectorySizeMismatch { expected: u64, found: u64 },
+    CentralDirectoryRecordCountMismatch { expected: u64, found: u64 },
+    MalformedLocalHeader { offset: u64, reason: lfh::LfhError },
+    MalformedRangeResponse,
+    ReadTimedOut(std::time::Duration),
+    RangeNotReturned { start: u64, end: u64 },
+    ExtractionFailed { name: String, reason: extract::ExtractError },
+    CentralDirectoryNotFound(u64),
+    VolumeNotFound(u32),
+    VolumeCountMismatch { expected: usize, found: usize },
 }
 
 impl Error for ZipSniperError {}
 
+impl ZipSniperError {
+    pub fn is_transient(&self) -> bool {
+        /*
+        Whether sending the same request again may succeed: timeouts, dropped or
+        reset connections, server errors (5xx), 408 and 429. Any other reply, such
+        as 403, 404 or 416, will not change, and neither will a failed TLS handshake
+        such as a certificate which does not verify.
+        */
+        match self {
+            ZipSniperError::HttpError(err) if ZipSniperError::is_tls_failure(err) => false,
+            ZipSniperError::HttpError(err) => {
+                err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+            }
+            ZipSniperError::InvalidStatusCode(status) => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            ZipSniperError::ReadTimedOut(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_tls_failure(err: &reqwest::Error) -> bool {
+        /*
+        Whether the TLS backend raised the error somewhere down its source chain
+        */
+        let mut source = err.source();
+        while let Some(err) = source {
+            if err.is::<native_tls::Error>() {
+                return true;
+            }
+            source = err.source();
+        }
+        false
+    }
+}
+
 impl Display for ZipSniperError {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         match self {
+            ZipSniperError::ClientError(err) => write!(f, "{}", err),
             ZipSniperError::HttpError(err) => write!(f, "HTTP Error: {}", err),
             ZipSniperError::InvalidStatusCode(status) => {
                 write!(f, "Invalid Status Code: {}", status)
             }
-            ZipSniperError::EocdSignatureNotFound => {
-                write!(f, "The comment buffer applied is too small!")
+            ZipSniperError::EocdSignatureNotFound { searched } => {
+                write!(f, "No EOCD found in the last {} bytes of the archive", searched)
+            }
+            ZipSniperError::Zip64LocatorNotFound => {
+                write!(f, "The EOCD requires ZIP64 but no ZIP64 EOCD locator was found")
+            }
+            ZipSniperError::Zip64EocdNotFound(offset) => {
+                write!(f, "No ZIP64 EOCD record found at offset {}", offset)
+            }
+            ZipSniperError::MalformedCentralDirectory { offset, reason } => {
+                write!(f, "Malformed CD record at offset {}: {}", offset, reason)
+            }
+            ZipSniperError::CentralDirectorySizeMismatch { expected, found } => {
+                write!(f, "Expected a CD of {} bytes but got {} bytes", expected, found)
+            }
+            ZipSniperError::CentralDirectoryRecordCountMismatch { expected, found } => {
+                write!(f, "Expected {} CD records but walked {}", expected, found)
+            }
+            ZipSniperError::MalformedLocalHeader { offset, reason } => {
+                write!(f, "Malformed local file header at offset {}: {}", offset, reason)
+            }
+            ZipSniperError::MalformedRangeResponse => {
+                write!(f, "Malformed reply to a range request")
+            }
+            ZipSniperError::ReadTimedOut(timeout) => {
+                write!(f, "The server sent nothing for {:.1}s", timeout.as_secs_f64())
+            }
+            ZipSniperError::RangeNotReturned { start, end } => {
+                write!(f, "The server did not return the bytes {} to {}", start, end)
+            }
+            ZipSniperError::ExtractionFailed { name, reason } => {
+                write!(f, "Failed to extract {}: {}", name, reason)
+            }
+            ZipSniperError::CentralDirectoryNotFound(offset) => {
+                write!(f, "No CD found at offset {} nor in front of the EOCD", offset)
+            }
+            ZipSniperError::VolumeNotFound(disk) => {
+                write!(f, "The archive refers to disk {} but no volume was given for it", disk)
+            }
+            ZipSniperError::VolumeCountMismatch { expected, found } => {
+                write!(f, "The archive spans {} disks but {} volumes were given", expected, found)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /*
+    Stored entries with a DOS date of 1980-01-01 and no extra fields
+    */
+    fn local_header(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = vec![0x50, 0x4b, 0x03, 0x04, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0];
+        header.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&[0, 0]);
+        header.extend_from_slice(name.as_bytes());
+        header
+    }
+
+    fn cd_record(name: &str, data: &[u8], offset: u32) -> Vec<u8> {
+        let mut record = vec![0x50, 0x4b, 0x01, 0x02, 20, 3, 20, 0, 0, 0, 0, 0, 0, 0, 0x21, 0];
+        record.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        record.extend_from_slice(&[0; 12]);
+        record.extend_from_slice(&offset.to_le_bytes());
+        record.extend_from_slice(name.as_bytes());
+        record
+    }
+
+    fn eocd_record(records: u16, cd_size: u32, cd_offset: u32, comment: &[u8]) -> Vec<u8> {
+        let mut record = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0];
+        record.extend_from_slice(&records.to_le_bytes());
+        record.extend_from_slice(&records.to_le_bytes());
+        record.extend_from_slice(&cd_size.to_le_bytes());
+        record.extend_from_slice(&cd_offset.to_le_bytes());
+        record.extend_from_slice(&(comment.len() as u16).to_le_bytes());
+        record.extend_from_slice(comment);
+        record
+    }
+
+    fn archive(entries: &[(&str, &[u8])], comment: &[u8]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut central_directory = Vec::new();
+        for (name, data) in entries {
+            central_directory.extend(cd_record(name, data, archive.len() as u32));
+            archive.extend(local_header(name, data));
+            archive.extend_from_slice(data);
+        }
+        let cd_offset = archive.len() as u32;
+        archive.extend_from_slice(&central_directory);
+        archive.extend(eocd_record(entries.len() as u16, central_directory.len() as u32, cd_offset, comment));
+        archive
+    }
+
+    fn sniper() -> ZipSniper {
+        ZipSniper::new(String::from("http://127.0.0.1:9/archive.zip"), None).unwrap()
+    }
+
+    #[test]
+    fn parse_out_eocd_skips_decoy_in_comment() {
+        let decoy = eocd_record(1, 10, 0, b"");
+        let comment = [&b"note "[..], &decoy, &b" trailing text"[..]].concat();
+        let archive = archive(&[("a.txt", b"hello")], &comment);
+
+        let (position, eocd, exact) = sniper().parse_out_eocd(&Bytes::from(archive.clone())).unwrap();
+        assert_eq!(position, archive.len() - eocd::Eocd::EOCD_32_SIZE - comment.len());
+        assert_eq!(eocd.zip_file_comment_length(), comment.len() as u64);
+        assert!(exact);
+    }
+
+    #[test]
+    fn parse_out_eocd_skips_truncated_trailing_eocd() {
+        let mut archive = archive(&[("a.txt", b"hello")], b"");
+        let position = archive.len() - eocd::Eocd::EOCD_32_SIZE;
+        archive.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+
+        let (found, _, exact) = sniper().parse_out_eocd(&Bytes::from(archive)).unwrap();
+        assert_eq!(found, position);
+        assert!(!exact);
+    }
+
+    #[test]
+    fn parse_out_eocd_without_signature() {
+        assert!(matches!(
+            sniper().parse_out_eocd(&Bytes::from(vec![0; 100])),
+            Err(ZipSniperError::EocdSignatureNotFound { searched: 100 })
+        ));
+    }
+
+    /*
+    Serves <archive> over HTTP on a local port, answering single range requests the
+    way a static file server does. Returns the URL of the archive and a counter of
+    the requests answered.
+    */
+    async fn serve(archive: Vec<u8>) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/archive.zip", listener.local_addr().unwrap());
+        let archive = std::sync::Arc::new(archive);
+        let requests = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let archive = archive.clone();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        let read = socket.read(&mut chunk).await.unwrap();
+                        if read == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&chunk[..read]);
+                    }
+                    let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+                    let range = request
+                        .lines()
+                        .find_map(|line| line.strip_prefix("range: bytes="))
+                        .unwrap()
+                        .trim()
+                        .to_string();
+                    let length = archive.len();
+                    let (start, end) = match range.split_once('-').unwrap() {
+                        ("", suffix) => (length.saturating_sub(suffix.parse().unwrap()), length - 1),
+                        (start, end) => (start.parse().unwrap(), end.parse::<usize>().unwrap().min(length - 1)),
+                    };
+                    let header = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        start, end, length, end + 1 - start
+                    );
+                    socket.write_all(header.as_bytes()).await.unwrap();
+                    socket.write_all(&archive[start..=end]).await.unwrap();
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn search_eocd_widens_past_long_comment() {
+        let comment = vec![b'c'; ZipSniper::CENTRAL_DIRECTORY_SEARCH_WINDOW as usize * 2];
+        let (url, requests) = serve(archive(&[("a.txt", b"hello")], &comment)).await;
+        let sniper = ZipSniper::new(url, None).unwrap();
+
+        let eocd = sniper.read_eocd(eocd::Eocd::EOCD_32_SIZE as u64).await.unwrap();
+        assert_eq!(eocd.zip_file_comment_length(), comment.len() as u64);
+        assert!(requests.load(std::sync::atomic::Ordering::SeqCst) > 1);
+        assert_eq!(sniper.read_cds(&eocd).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_eocd_gives_up_at_the_limit() {
+        let mut archive = archive(&[("a.txt", b"hello")], b"");
+        archive.extend(vec![0; ZipSniper::EOCD_SEARCH_LIMIT as usize]);
+        let (url, _) = serve(archive).await;
+        let sniper = ZipSniper::new(url, None).unwrap();
+
+        match sniper.read_eocd(eocd::Eocd::EOCD_32_SIZE as u64).await {
+            Err(ZipSniperError::EocdSignatureNotFound { searched }) => {
+                assert_eq!(searched, ZipSniper::EOCD_SEARCH_LIMIT)
+            }
+            other => panic!("expected EocdSignatureNotFound, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn locate_central_directory_finds_prepended_stub() {
+        let entries: [(&str, &[u8]); 2] = [("a.txt", b"hello"), ("b.txt", b"world!")];
+        let stub = vec![b'#'; 3000];
+        let (url, _) = serve([stub.clone(), archive(&entries, b"")].concat()).await;
+        let sniper = ZipSniper::new(url, None).unwrap();
+
+        let eocd = sniper.read_eocd(1024).await.unwrap();
+        assert_eq!(eocd.base_offset, stub.len() as u64);
+
+        let cds = sniper.read_cds(&eocd).await.unwrap();
+        assert_eq!(sniper.local_header_offset(&cds[0]).unwrap(), stub.len() as u64);
+        assert_eq!(
+            sniper.local_header_offset(&cds[1]).unwrap(),
+            (stub.len() + local_header("a.txt", b"hello").len() + 5) as u64
+        );
+        assert_eq!(sniper.extract_entry(&cds[1]).await.unwrap(), Bytes::from_static(b"world!"));
+    }
+
+    #[tokio::test]
+    async fn locate_central_directory_without_stub() {
+        let (url, _) = serve(archive(&[("a.txt", b"hello")], b"")).await;
+        let sniper = ZipSniper::new(url, None).unwrap();
+        assert_eq!(sniper.read_eocd(1024).await.unwrap().base_offset, 0);
+    }
+
+    fn central_directory(count: usize) -> Vec<u8> {
+        (0..count).flat_map(|index| cd_record(&format!("{}.txt", index), b"x", 0)).collect()
+    }
+
+    fn eocd(records: u16, cd_size: usize) -> eocd::Eocd {
+        eocd::Eocd::from(Bytes::from(eocd_record(records, cd_size as u32, 0, b"")))
+    }
+
+    #[test]
+    fn parse_out_cds_walks_every_record() {
+        let central_directory = central_directory(3);
+        let cds = sniper()
+            .parse_out_cds(Bytes::from(central_directory.clone()), 0, &eocd(3, central_directory.len()))
+            .unwrap();
+        let names: Vec<String> = cds.iter().map(|cd| cd.file_name_lossy()).collect();
+        assert_eq!(names, ["0.txt", "1.txt", "2.txt"]);
+    }
+
+    #[test]
+    fn parse_out_cds_size_mismatch() {
+        let central_directory = central_directory(2);
+        let length = central_directory.len() as u64;
+        assert!(matches!(
+            sniper().parse_out_cds(Bytes::from(central_directory), 0, &eocd(2, length as usize + 1)),
+            Err(ZipSniperError::CentralDirectorySizeMismatch { expected, found }) if expected == length + 1 && found == length
+        ));
+    }
+
+    #[test]
+    fn parse_out_cds_count_mismatch() {
+        let central_directory = central_directory(2);
+        let length = central_directory.len();
+        assert!(matches!(
+            sniper().parse_out_cds(Bytes::from(central_directory), 0, &eocd(3, length)),
+            Err(ZipSniperError::CentralDirectoryRecordCountMismatch { expected: 3, found: 2 })
+        ));
+    }
+
+    #[test]
+    fn parse_out_cds_truncated_record() {
+        let mut central_directory = central_directory(1);
+        let record_length = central_directory.len() as u64;
+        central_directory.extend_from_slice(&cd_record("b.txt", b"x", 0)[..20]);
+        let length = central_directory.len();
+        assert!(matches!(
+            sniper().parse_out_cds(Bytes::from(central_directory), 1000, &eocd(2, length)),
+            Err(ZipSniperError::MalformedCentralDirectory { offset, reason: cd::CdError::Truncated })
+                if offset == 1000 + record_length
+        ));
+    }
+
+    #[test]
+    fn parse_out_cds_accepts_wrapped_count() {
+        let central_directory = central_directory(0x10001);
+        let length = central_directory.len();
+        let cds = sniper().parse_out_cds(Bytes::from(central_directory), 0, &eocd(1, length)).unwrap();
+        assert_eq!(cds.len(), 0x10001);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::cd::Cd;
+use crate::extra::ExtraField;
+use crate::flags::GeneralPurposeFlags;
+use crate::lfh::Lfh;
+
+/*
+Length of the data descriptor most writers put down, signed with 32-bit sizes,
+and of the one with 64-bit sizes
+*/
+const DATA_DESCRIPTOR_SIZE: u64 = 16;
+const DATA_DESCRIPTOR_64_SIZE: u64 = 24;
+
+/*
+The byte range an entry occupies within the remote file:
+[local_header_offset, data_offset) holds the local file header,
+[data_offset, data_end) holds the compressed data and the data descriptor,
+if any, takes up the `descriptor_length` bytes after it.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntrySpan {
+    /*
+    Position of the entry within the Central Directory.
+    */
+    pub index: usize,
+    pub local_header_offset: u64,
+    pub data_offset: u64,
+    pub data_end: u64,
+    /*
+    Length of the data descriptor following the compressed data, 0 without one.
+    */
+    pub descriptor_length: u64,
+    /*
+    The local header length was taken from the Central Directory lengths
+    instead of the local file header itself.
+    */
+    pub data_offset_estimated: bool,
+    /*
+    Bytes between the end of the previous span and the start of this one.
+    */
+    pub gap_before: u64,
+    /*
+    CD index of an earlier span this one starts inside of.
+    */
+    pub overlaps: Option<usize>,
+}
+
+/*
+Every entry span of an archive, sorted by local header offset.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveMap {
+    pub entries: Vec<EntrySpan>,
+    pub central_directory_offset: u64,
+    /*
+    Bytes between the end of the last span and the start of the Central Directory.
+    */
+    pub gap_before_central_directory: u64,
+    /*
+    Bytes the furthest span runs into the Central Directory.
+    */
+    pub central_directory_overlap: u64,
+}
+
+impl EntrySpan {
+    pub fn from_cd(index: usize, local_header_offset: u64, cd: &Cd) -> Self {
+        /*
+        Estimates the span of an entry from its Central Directory record.
+        The local file header usually repeats the file name and extra field
+        lengths of the CD, but writers are free to put a different extra field there.
+        The data descriptor of an entry with general purpose bit 3 set is taken to be
+        signed, with 64-bit sizes when the CD record carries ZIP64 extended information.
+
+        # Arguments
+        * `index: usize` The position of the entry within the Central Directory
+        * `local_header_offset: u64` The absolute offset of the local file header
+        * `cd: &Cd` The Central Directory record of the entry
+        */
+        let local_header_length = Lfh::HEADER_SIZE as u64
+            + cd.file_name_length() as u64
+            + cd.extra_field_length() as u64;
+        let mut span = Self::new(index, local_header_offset, local_header_length, cd);
+        span.data_offset_estimated = true;
+        if cd.flags().contains(GeneralPurposeFlags::DATA_DESCRIPTOR) {
+            let zip64 = cd
+                .extra_fields()
+                .any(|field| matches!(field, ExtraField::Zip64ExtendedInformation(_)));
+            span.descriptor_length = match zip64 {
+                true => DATA_DESCRIPTOR_64_SIZE,
+                false => DATA_DESCRIPTOR_SIZE,
+            };
+        }
+        span
+    }
+
+    pub fn new(index: usize, local_header_offset: u64, local_header_length: u64, cd: &Cd) -> Self {
+        /*
+        # Arguments
+        * `index: usize` The position of the entry within the Central Directory
+        * `local_header_offset: u64` The absolute offset of the local file header
+        * `local_header_length: u64` The full length of the local file header
+        * `cd: &Cd` The Central Directory record of the entry
+        */
+        let data_offset = local_header_offset + local_header_length;
+        Self {
+            index,
+            local_header_offset,
+            data_offset,
+            data_end: data_offset + cd.compressed_size(),
+            descriptor_length: 0,
+            data_offset_estimated: false,
+            gap_before: 0,
+            overlaps: None,
+        }
+    }
+
+    pub fn with_descriptor_length(mut self, descriptor_length: u64) -> Self {
+        /*
+        Sets the length of the data descriptor following the compressed data
+        */
+        self.descriptor_length = descriptor_length;
+        self
+    }
+
+    pub fn end(&self) -> u64 {
+        /*
+        The end of the entry, past its data descriptor
+        */
+        self.data_end + self.descriptor_length
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end() - self.local_header_offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ArchiveMap {
+    pub fn from_spans(mut spans: Vec<EntrySpan>, central_directory_offset: u64) -> Self {
+        /*
+        Sorts the spans by offset, then marks every gap and overlap between them.
+        The region in front of the first entry counts as a gap as well, as the first
+        local file header should sit at offset 0.
+
+        # Arguments
+        * `spans: Vec<EntrySpan>` The span of every entry
+        * `central_directory_offset: u64` The absolute offset of the Central Directory
+        */
+        spans.sort_by_key(|span| (span.local_header_offset, span.index));
+
+        let mut furthest_end = 0;
+        let mut furthest_index = None;
+        for span in spans.iter_mut() {
+            span.gap_before = span.local_header_offset.saturating_sub(furthest_end);
+            span.overlaps = match span.local_header_offset < furthest_end {
+                true => furthest_index,
+                false => None,
+            };
+
+            if span.end() >= furthest_end {
+                furthest_end = span.end();
+                furthest_index = Some(span.index);
+            }
+        }
+
+        Self {
+            entries: spans,
+            central_directory_offset,
+            gap_before_central_directory: central_directory_offset.saturating_sub(furthest_end),
+            central_directory_overlap: furthest_end.saturating_sub(central_directory_offset),
+        }
+    }
+
+    pub fn span(&self, index: usize) -> Option<&EntrySpan> {
+        /*
+        Returns the span of the entry at position <index> in the Central Directory
+        */
+        self.entries.iter().find(|span| span.index == index)
+    }
+
+    pub fn gaps(&self) -> impl Iterator<Item = &EntrySpan> {
+        self.entries.iter().filter(|span| span.gap_before > 0)
+    }
+
+    pub fn overlaps(&self) -> impl Iterator<Item = &EntrySpan> {
+        self.entries.iter().filter(|span| span.overlaps.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(index: usize, local_header_offset: u64, end: u64) -> EntrySpan {
+        EntrySpan {
+            index,
+            local_header_offset,
+            data_offset: local_header_offset + Lfh::HEADER_SIZE as u64,
+            data_end: end,
+            descriptor_length: 0,
+            data_offset_estimated: false,
+            gap_before: 0,
+            overlaps: None,
+        }
+    }
+
+    #[test]
+    fn from_spans_without_gaps() {
+        let map = ArchiveMap::from_spans(vec![span(0, 0, 100), span(1, 100, 250)], 250);
+        assert_eq!(map.gaps().count(), 0);
+        assert_eq!(map.overlaps().count(), 0);
+        assert_eq!(map.gap_before_central_directory, 0);
+        assert_eq!(map.central_directory_overlap, 0);
+    }
+
+    #[test]
+    fn from_spans_sorts_by_offset() {
+        let map = ArchiveMap::from_spans(vec![span(0, 100, 200), span(1, 0, 100)], 200);
+        let indices: Vec<usize> = map.entries.iter().map(|span| span.index).collect();
+        assert_eq!(indices, vec![1, 0]);
+        assert_eq!(map.span(0).unwrap().local_header_offset, 100);
+    }
+
+    #[test]
+    fn from_spans_marks_gaps() {
+        let map = ArchiveMap::from_spans(vec![span(0, 16, 100), span(1, 120, 200)], 232);
+        let gaps: Vec<(usize, u64)> = map.gaps().map(|span| (span.index, span.gap_before)).collect();
+        assert_eq!(gaps, vec![(0, 16), (1, 20)]);
+        assert_eq!(map.overlaps().count(), 0);
+        assert_eq!(map.gap_before_central_directory, 32);
+    }
+
+    #[test]
+    fn from_spans_marks_overlaps() {
+        let map = ArchiveMap::from_spans(vec![span(0, 0, 300), span(1, 100, 200), span(2, 250, 400)], 350);
+        let overlaps: Vec<(usize, Option<usize>)> =
+            map.overlaps().map(|span| (span.index, span.overlaps)).collect();
+        assert_eq!(overlaps, vec![(1, Some(0)), (2, Some(0))]);
+        assert_eq!(map.gaps().count(), 0);
+        assert_eq!(map.central_directory_overlap, 50);
+        assert_eq!(map.gap_before_central_directory, 0);
+    }
+
+    #[test]
+    fn from_spans_measures_gaps_from_the_furthest_end() {
+        let map = ArchiveMap::from_spans(vec![span(0, 0, 300), span(1, 100, 200), span(2, 310, 400)], 400);
+        let span = map.span(2).unwrap();
+        assert_eq!(span.gap_before, 10);
+        assert_eq!(span.overlaps, None);
+    }
+
+    #[test]
+    fn from_spans_counts_the_descriptor() {
+        let spans = vec![span(0, 0, 100).with_descriptor_length(16), span(1, 108, 200)];
+        let map = ArchiveMap::from_spans(spans, 200);
+        assert_eq!(map.span(1).unwrap().overlaps, Some(0));
+        assert_eq!(map.span(1).unwrap().gap_before, 0);
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::extra::{ExtraField, ExtraFields};
+
+/*
+Seconds between the NTFS epoch (1601-01-01) and the Unix epoch (1970-01-01)
+*/
+const NTFS_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/*
+NTFS times count 100ns intervals
+*/
+const NTFS_TICKS_PER_SECOND: u64 = 10_000_000;
+
+/*
+Where a timestamp of an entry was taken from.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampSource {
+    /*
+    The DOS date and time fields of the header, 2 second precision.
+    */
+    Dos,
+    /*
+    The NTFS extra field (0x000a), 100ns precision.
+    */
+    Ntfs,
+    /*
+    The extended timestamp extra field (0x5455), 1 second precision.
+    */
+    ExtendedTimestamp,
+    /*
+    The original Info-ZIP Unix extra field (0x5855), 1 second precision.
+    */
+    InfoZipUnix,
+}
+
+/*
+A point in time recorded for an entry.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    pub date_time: NaiveDateTime,
+    /*
+    `date_time` is the local time of the machine that wrote the archive, in a
+    time zone the archive does not record. Otherwise it is UTC.
+    */
+    pub timezone_unknown: bool,
+    pub source: TimestampSource,
+}
+
+/*
+The times of an entry, each one taken from the most precise source holding it.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EntryTimes {
+    pub modified: Option<Timestamp>,
+    pub accessed: Option<Timestamp>,
+    pub created: Option<Timestamp>,
+}
+
+impl std::fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimestampSource::Dos => write!(f, "DOS"),
+            TimestampSource::Ntfs => write!(f, "NTFS"),
+            TimestampSource::ExtendedTimestamp => write!(f, "extended timestamp"),
+            TimestampSource::InfoZipUnix => write!(f, "Info-ZIP Unix"),
+        }
+    }
+}
+
+impl Timestamp {
+    pub fn from_dos(date: u16, time: u16) -> Option<Self> {
+        /*
+        Date: bits 9-15 year since 1980, bits 5-8 month, bits 0-4 day
+        Time: bits 11-15 hour, bits 5-10 minute, bits 0-4 second / 2
+
+        Returns None for values which are not a valid date-time, such as the
+        all zero fields some writers leave behind.
+        */
+        let date_time = NaiveDate::from_ymd_opt(
+            1980 + (date >> 9) as i32,
+            ((date >> 5) & 0x0F) as u32,
+            (date & 0x1F) as u32,
+        )?
+        .and_hms_opt(
+            (time >> 11) as u32,
+            ((time >> 5) & 0x3F) as u32,
+            (time & 0x1F) as u32 * 2,
+        )?;
+
+        Some(Self {
+            date_time,
+            timezone_unknown: true,
+            source: TimestampSource::Dos,
+        })
+    }
+
+    pub fn from_ntfs(ticks: u64) -> Option<Self> {
+        /*
+        Returns None for 0, which NTFS uses for a time that was never set
+        */
+        if ticks == 0 {
+            return None;
+        }
+        let seconds = (ticks / NTFS_TICKS_PER_SECOND) as i64 - NTFS_EPOCH_OFFSET;
+        let nanoseconds = (ticks % NTFS_TICKS_PER_SECOND) as u32 * 100;
+        let date_time = DateTime::<Utc>::from_timestamp(seconds, nanoseconds)?;
+
+        Some(Self {
+            date_time: date_time.naive_utc(),
+            timezone_unknown: false,
+            source: TimestampSource::Ntfs,
+        })
+    }
+
+    pub fn from_unix(seconds: i64, source: TimestampSource) -> Option<Self> {
+        let date_time = DateTime::<Utc>::from_timestamp(seconds, 0)?;
+
+        Some(Self {
+            date_time: date_time.naive_utc(),
+            timezone_unknown: false,
+            source,
+        })
+    }
+
+    pub fn utc(&self) -> Option<DateTime<Utc>> {
+        /*
+        The timestamp as UTC, None when its time zone is unknown
+        */
+        match self.timezone_unknown {
+            true => None,
+            false => Some(self.date_time.and_utc()),
+        }
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.timezone_unknown {
+            true => write!(f, "{}", self.date_time.format("%Y-%m-%d %H:%M:%S")),
+            false => write!(f, "{} UTC", self.date_time.format("%Y-%m-%d %H:%M:%S%.f")),
+        }
+    }
+}
+
+impl EntryTimes {
+    pub fn new(dos_date: u16, dos_time: u16, extra_fields: ExtraFields) -> Self {
+        /*
+        Collects the times of an entry from its header and extra field. Every time is
+        taken from the most precise source holding it, in the order NTFS, extended
+        timestamp, Info-ZIP Unix. The modification time falls back to the DOS fields.
+
+        # Arguments
+        * `dos_date: u16` The DOS last modification date of the header
+        * `dos_time: u16` The DOS last modification time of the header
+        * `extra_fields: ExtraFields` The extra field of the same header
+        */
+        let mut ntfs = Self::default();
+        let mut extended = Self::default();
+        let mut unix = Self::default();
+
+        for field in extra_fields {
+            match field {
+                ExtraField::Ntfs(times) => {
+                    ntfs.modified = Timestamp::from_ntfs(times.modification_time);
+                    ntfs.accessed = Timestamp::from_ntfs(times.access_time);
+                    ntfs.created = Timestamp::from_ntfs(times.creation_time);
+                }
+                ExtraField::ExtendedTimestamp(times) => {
+                    let decode = |seconds: Option<i32>| {
+                        Timestamp::from_unix(seconds? as i64, TimestampSource::ExtendedTimestamp)
+                    };
+                    extended.modified = decode(times.modification_time);
+                    extended.accessed = decode(times.access_time);
+                    extended.created = decode(times.creation_time);
+                }
+                ExtraField::InfoZipUnixLegacy(times) => {
+                    let decode = |seconds: i32| Timestamp::from_unix(seconds as i64, TimestampSource::InfoZipUnix);
+                    unix.modified = decode(times.modification_time);
+                    unix.accessed = decode(times.access_time);
+                }
+                _ => (),
+            }
+        }
+
+        Self {
+            modified: ntfs
+                .modified
+                .or(extended.modified)
+                .or(unix.modified)
+                .or_else(|| Timestamp::from_dos(dos_date, dos_time)),
+            accessed: ntfs.accessed.or(extended.accessed).or(unix.accessed),
+            created: ntfs.created.or(extended.created),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+
+    fn date_time(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, second)
+            .unwrap()
+    }
+
+    fn ntfs(modification_time: u64) -> Vec<u8> {
+        let mut binary = vec![0x0a, 0x00, 32, 0, 0, 0, 0, 0, 0x01, 0x00, 24, 0];
+        binary.extend_from_slice(&modification_time.to_le_bytes());
+        binary.extend_from_slice(&[0; 16]);
+        binary
+    }
+
+    #[test]
+    fn dos_epoch() {
+        let timestamp = Timestamp::from_dos(0x0021, 0x0000).unwrap();
+        assert_eq!(timestamp.date_time, date_time(1980, 1, 1, 0, 0, 0));
+        assert!(timestamp.timezone_unknown);
+        assert_eq!(timestamp.utc(), None);
+        assert_eq!(timestamp.to_string(), "1980-01-01 00:00:00");
+    }
+
+    #[test]
+    fn dos_seconds_count_in_steps_of_two() {
+        // 2024-02-29 23:59:58, the seconds field holding 29
+        let timestamp = Timestamp::from_dos(22621, 49021).unwrap();
+        assert_eq!(timestamp.date_time, date_time(2024, 2, 29, 23, 59, 58));
+
+        // 2021-03-14 10:30:14
+        let timestamp = Timestamp::from_dos(21102, 21447).unwrap();
+        assert_eq!(timestamp.date_time, date_time(2021, 3, 14, 10, 30, 14));
+    }
+
+    #[test]
+    fn dos_rejects_invalid_fields() {
+        assert_eq!(Timestamp::from_dos(0, 0), None);
+        // February 30th
+        assert_eq!(Timestamp::from_dos((44 << 9) | (2 << 5) | 30, 0), None);
+        // 24 o'clock
+        assert_eq!(Timestamp::from_dos(0x0021, 24 << 11), None);
+    }
+
+    #[test]
+    fn ntfs_keeps_100ns_precision() {
+        let timestamp = Timestamp::from_ntfs(132_341_967_301_234_567).unwrap();
+        assert_eq!(
+            timestamp.date_time,
+            date_time(2020, 5, 17, 13, 45, 30) + chrono::Duration::nanoseconds(123_456_700)
+        );
+        assert_eq!(timestamp.utc().unwrap().timestamp(), 1_589_723_130);
+        assert_eq!(timestamp.to_string(), "2020-05-17 13:45:30.123456700 UTC");
+    }
+
+    #[test]
+    fn ntfs_before_the_unix_epoch() {
+        let timestamp = Timestamp::from_ntfs(113_288_544_000_000_005).unwrap();
+        assert_eq!(
+            timestamp.date_time,
+            date_time(1960, 1, 1, 0, 0, 0) + chrono::Duration::nanoseconds(500)
+        );
+        assert_eq!(timestamp.utc().unwrap().timestamp(), -315_619_200);
+
+        let timestamp = Timestamp::from_ntfs(NTFS_TICKS_PER_SECOND).unwrap();
+        assert_eq!(timestamp.date_time, date_time(1601, 1, 1, 0, 0, 1));
+    }
+
+    #[test]
+    fn ntfs_zero_is_unset() {
+        assert_eq!(Timestamp::from_ntfs(0), None);
+    }
+
+    #[test]
+    fn unix_before_the_epoch() {
+        let timestamp = Timestamp::from_unix(-1, TimestampSource::ExtendedTimestamp).unwrap();
+        assert_eq!(timestamp.date_time, date_time(1969, 12, 31, 23, 59, 59));
+        assert_eq!(timestamp.source, TimestampSource::ExtendedTimestamp);
+    }
+
+    #[test]
+    fn entry_times_fall_back_to_dos() {
+        let times = EntryTimes::new(0x0021, 0, ExtraFields::new(Bytes::new()));
+        assert_eq!(times.modified.unwrap().source, TimestampSource::Dos);
+        assert_eq!(times.accessed, None);
+        assert_eq!(times.created, None);
+    }
+
+    #[test]
+    fn entry_times_prefer_ntfs_over_dos() {
+        let times = EntryTimes::new(0x0021, 0, ExtraFields::new(Bytes::from(ntfs(132_341_967_301_234_567))));
+        let modified = times.modified.unwrap();
+        assert_eq!(modified.source, TimestampSource::Ntfs);
+        assert_eq!(modified.utc().unwrap().timestamp(), 1_589_723_130);
+    }
+}